@@ -1,8 +1,14 @@
 // Copyright (c), Mysten Labs, Inc.
 // SPDX-License-Identifier: Apache-2.0
 
+pub mod attestation_cache;
+pub mod clock;
+pub mod config;
+
 use crate::AppState;
 use crate::EnclaveError;
+use axum::http::{HeaderValue, Method};
+use axum::response::IntoResponse;
 use axum::{extract::State, Json};
 use fastcrypto::traits::Signer;
 use fastcrypto::{encoding::Encoding, traits::ToFromBytes};
@@ -18,11 +24,15 @@ use serde::{Deserialize, Serialize};
 use serde_bytes::ByteBuf;
 use serde_repr::Deserialize_repr;
 use serde_repr::Serialize_repr;
+use sha2::{Digest, Sha256};
 use std::collections::HashMap;
 use std::fmt::Debug;
+use std::net::{IpAddr, SocketAddr};
 use std::sync::Arc;
 use std::time::Duration;
-use tracing::info;
+use subtle::ConstantTimeEq;
+use tower_http::cors::{Any, CorsLayer};
+use tracing::{info, warn};
 
 use fastcrypto::ed25519::Ed25519KeyPair;
 /// ==== COMMON TYPES ====
@@ -95,52 +105,259 @@ pub struct GetAttestationResponse {
     pub attestation: String,
 }
 
-/// Endpoint that returns an attestation committed
-/// to the enclave's public key.
-#[cfg(feature = "nitro")]
-pub async fn get_attestation(
-    State(state): State<Arc<AppState>>,
-) -> Result<Json<GetAttestationResponse>, EnclaveError> {
-    info!("get attestation called");
+/// Request an NSM attestation document committing to `public_key`.
+pub fn attestation_document(public_key: &[u8]) -> Result<Vec<u8>, EnclaveError> {
+    attestation_document_with_user_data(public_key, None)
+}
 
-    let pk = state.eph_kp.public();
+/// Request an NSM attestation document committing to `public_key` and,
+/// when given, `user_data`.
+#[cfg(feature = "nitro")]
+pub fn attestation_document_with_user_data(
+    public_key: &[u8],
+    user_data: Option<&[u8]>,
+) -> Result<Vec<u8>, EnclaveError> {
     let fd = driver::nsm_init();
 
     // Send attestation request to NSM driver with public key set.
     let request = NsmRequest::Attestation {
-        user_data: None,
+        user_data: user_data.map(|data| ByteBuf::from(data.to_vec())),
         nonce: None,
-        public_key: Some(ByteBuf::from(pk.as_bytes().to_vec())),
+        public_key: Some(ByteBuf::from(public_key.to_vec())),
     };
 
     let response = driver::nsm_process_request(fd, request);
+    driver::nsm_exit(fd);
     match response {
-        NsmResponse::Attestation { document } => {
-            driver::nsm_exit(fd);
-            Ok(Json(GetAttestationResponse {
-                attestation: Hex::encode(document),
-            }))
-        }
-        _ => {
-            driver::nsm_exit(fd);
-            Err(EnclaveError::GenericError(
-                "unexpected response".to_string(),
-            ))
-        }
+        NsmResponse::Attestation { document } => Ok(document),
+        _ => Err(EnclaveError::GenericError(
+            "unexpected response".to_string(),
+        )),
     }
 }
 
 /// Stub for non-nitro builds
 #[cfg(not(feature = "nitro"))]
-pub async fn get_attestation(
-    State(_state): State<Arc<AppState>>,
-) -> Result<Json<GetAttestationResponse>, EnclaveError> {
-    info!("get_attestation called (stub - nitro feature not enabled)");
+pub fn attestation_document_with_user_data(
+    _public_key: &[u8],
+    _user_data: Option<&[u8]>,
+) -> Result<Vec<u8>, EnclaveError> {
     Err(EnclaveError::GenericError(
         "attestation not available in non-nitro builds".to_string(),
     ))
 }
 
+/// Attestation committing to the ephemeral key in `public_key` and, in
+/// orders builds, to the raw order-signing public key in `user_data`. That
+/// binding is what lets a relying party trust signed orders: the attested
+/// PCRs vouch for the enclave, and the enclave vouches for the key. With
+/// per-merchant keys, `user_data` continues with
+/// `crypto::merchant_keys_digest`, so the merchant keys listed by
+/// `/orders/health` can be checked against the same document.
+pub fn attestation_with_pubkey(public_key: &[u8]) -> Result<Vec<u8>, EnclaveError> {
+    attest_keys(public_key, attestation_document_with_user_data)
+}
+
+/// `attestation_with_pubkey` with the document produced by `attest` from
+/// the public key and user data, rather than by the NSM.
+fn attest_keys(
+    public_key: &[u8],
+    attest: impl FnOnce(&[u8], Option<&[u8]>) -> Result<Vec<u8>, EnclaveError>,
+) -> Result<Vec<u8>, EnclaveError> {
+    #[cfg(feature = "orders")]
+    {
+        attest(public_key, Some(&signing_keys()?))
+    }
+    #[cfg(not(feature = "orders"))]
+    attest(public_key, None)
+}
+
+/// Raw order-signing public key, followed by the merchant keys digest when
+/// merchant keys are in use.
+#[cfg(feature = "orders")]
+fn signing_keys() -> Result<Vec<u8>, EnclaveError> {
+    let mut keys =
+        crate::orders::crypto::public_key_bytes().map_err(crate::orders::order::key_unavailable)?;
+    if let Some(digest) = crate::orders::crypto::merchant_keys_digest() {
+        keys.extend(digest);
+    }
+    Ok(keys)
+}
+
+/// Startup self-test: an enclave that cannot attest cannot prove which key
+/// it signs with. A failing `attest` refuses startup when `required`, and
+/// only warns otherwise.
+pub fn attestation_self_test(
+    required: bool,
+    attest: impl FnOnce() -> Result<Vec<u8>, EnclaveError>,
+) -> Result<(), EnclaveError> {
+    match attest() {
+        Ok(_) => {
+            info!("✅ Startup attestation self-test passed");
+            Ok(())
+        }
+        Err(e) if required => Err(e),
+        Err(e) => {
+            warn!(error = %e, "Attestation self-test failed; continuing without it");
+            Ok(())
+        }
+    }
+}
+
+/// PCR0 (enclave image), PCR1 (kernel and bootstrap) and PCR2 (application)
+/// as hex, the measurements a client pins a known-good enclave image by.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PcrMeasurements {
+    pub pcr0: String,
+    pub pcr1: String,
+    pub pcr2: String,
+}
+
+/// The enclave's PCR0-2, read from the NSM once and reused: they are fixed
+/// for the enclave's lifetime. `None` outside an enclave or if the NSM
+/// cannot be read.
+#[cfg(feature = "nitro")]
+pub fn pcr_measurements() -> Option<PcrMeasurements> {
+    fn describe(fd: i32, index: u16) -> Option<String> {
+        match driver::nsm_process_request(fd, NsmRequest::DescribePCR { index }) {
+            NsmResponse::DescribePCR { data, .. } => Some(Hex::encode(data)),
+            other => {
+                warn!(index, response = ?other, "Could not read PCR from the NSM");
+                None
+            }
+        }
+    }
+
+    static PCRS: once_cell::sync::Lazy<Option<PcrMeasurements>> =
+        once_cell::sync::Lazy::new(|| {
+            let fd = driver::nsm_init();
+            let pcrs = describe(fd, 0).and_then(|pcr0| {
+                Some(PcrMeasurements {
+                    pcr0,
+                    pcr1: describe(fd, 1)?,
+                    pcr2: describe(fd, 2)?,
+                })
+            });
+            driver::nsm_exit(fd);
+            pcrs
+        });
+    PCRS.clone()
+}
+
+/// Stub for non-nitro builds: there are no measurements to report.
+#[cfg(not(feature = "nitro"))]
+pub fn pcr_measurements() -> Option<PcrMeasurements> {
+    None
+}
+
+/// Every key `attestation_with_pubkey` commits to, so a cached document is
+/// reused only while none of them has changed (e.g. by key rotation).
+fn attested_keys(public_key: &[u8]) -> Result<Vec<u8>, EnclaveError> {
+    #[allow(unused_mut)]
+    let mut keys = public_key.to_vec();
+    #[cfg(feature = "orders")]
+    keys.extend(signing_keys()?);
+    Ok(keys)
+}
+
+/// CORS policy for `Config::cors_origins`, exact origins such as
+/// `https://app.example.com`, allowing only `GET` and `POST`. Without an
+/// allowlist this falls back to the permissive `crate::cors_layer()` and
+/// logs a warning.
+pub fn build_cors(origins: Option<&[HeaderValue]>) -> CorsLayer {
+    let Some(origins) = origins else {
+        warn!("CORS_ALLOWED_ORIGINS is unset; allowing any origin");
+        return crate::cors_layer();
+    };
+    info!(
+        origins = origins.len(),
+        "CORS restricted to allowlisted origins"
+    );
+    CorsLayer::new()
+        .allow_origin(origins.to_vec())
+        .allow_methods([Method::GET, Method::POST])
+        .allow_headers(Any)
+}
+
+/// Accept only a bare `http(s)://host[:port]` origin, which is what browsers
+/// send and compare against byte for byte.
+fn parse_origin(origin: &str) -> Result<HeaderValue, String> {
+    let host = origin
+        .strip_prefix("https://")
+        .or_else(|| origin.strip_prefix("http://"));
+    let well_formed = host.is_some_and(|host| {
+        !host.is_empty()
+            && !host.contains(|c: char| matches!(c, '/' | '?' | '#') || c.is_whitespace())
+    });
+    if !well_formed {
+        return Err(format!(
+            "CORS_ALLOWED_ORIGINS entry {:?} is not an origin like https://example.com",
+            origin
+        ));
+    }
+    HeaderValue::from_str(origin).map_err(|_| {
+        format!(
+            "CORS_ALLOWED_ORIGINS entry {:?} is not a valid header value",
+            origin
+        )
+    })
+}
+
+/// Socket address to listen on, from the `BIND_ADDR` and `PORT` values.
+/// Accepts IPv4 and IPv6 addresses, not hostnames.
+pub fn listen_addr(bind_addr: &str, port: &str) -> Result<SocketAddr, String> {
+    let ip = bind_addr
+        .trim()
+        .parse::<IpAddr>()
+        .map_err(|_| format!("BIND_ADDR {:?} is not an IP address", bind_addr))?;
+    let port = port
+        .trim()
+        .parse::<u16>()
+        .map_err(|_| format!("PORT {:?} is not a valid port number", port))?;
+    Ok(SocketAddr::new(ip, port))
+}
+
+/// Whether `provided` matches the configured API key `expected`. Compares
+/// SHA-256 digests in constant time, so neither the matching prefix nor the
+/// key's length shows in the timing.
+pub fn verify_api_key(provided: &str, expected: &str) -> bool {
+    let provided = Sha256::digest(provided.as_bytes());
+    let expected = Sha256::digest(expected.as_bytes());
+    provided.ct_eq(&expected).into()
+}
+
+/// Liveness probe at `/`.
+pub async fn ping() -> &'static str {
+    info!("📍 Ping endpoint called");
+    "Pong!"
+}
+
+/// Endpoint that returns an attestation committed
+/// to the enclave's public key and, in orders builds, its signing key.
+/// Served from `AppState::attestation_cache`, with the document's age in
+/// the `x-attestation-age-ms` header.
+pub async fn get_attestation(
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, EnclaveError> {
+    info!("get attestation called");
+
+    let pk = state.eph_kp.public();
+    let (document, age) = state
+        .attestation_cache
+        .get_or_refresh(attested_keys(pk.as_bytes())?, || {
+            attestation_with_pubkey(pk.as_bytes())
+        })?;
+    Ok((
+        [(
+            attestation_cache::ATTESTATION_AGE_HEADER,
+            age.as_millis().to_string(),
+        )],
+        Json(GetAttestationResponse {
+            attestation: Hex::encode(document),
+        }),
+    ))
+}
+
 /// Health check response.
 #[derive(Debug, Serialize, Deserialize)]
 pub struct HealthCheckResponse {
@@ -148,6 +365,8 @@ pub struct HealthCheckResponse {
     pub pk: String,
     /// Status of endpoint connectivity checks
     pub endpoints_status: HashMap<String, bool>,
+    /// Enclave PCR measurements; `null` outside a Nitro enclave.
+    pub pcrs: Option<PcrMeasurements>,
 }
 
 /// Endpoint that health checks the enclave connectivity to all
@@ -236,5 +455,90 @@ pub async fn health_check(
     Ok(Json(HealthCheckResponse {
         pk: Hex::encode(pk.as_bytes()),
         endpoints_status,
+        pcrs: pcr_measurements(),
     }))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn failing_provider() -> Result<Vec<u8>, EnclaveError> {
+        Err(EnclaveError::GenericError("nsm unavailable".to_string()))
+    }
+
+    #[test]
+    fn failing_attestation_refuses_startup_when_required() {
+        assert!(attestation_self_test(true, failing_provider).is_err());
+        assert!(attestation_self_test(false, failing_provider).is_ok());
+        assert!(attestation_self_test(true, || Ok(vec![1, 2, 3])).is_ok());
+    }
+
+    #[cfg(feature = "orders")]
+    #[test]
+    fn attestation_commits_to_the_signing_key() {
+        use crate::orders::crypto;
+        fn fixed_seed(seed: &mut [u8; 32]) -> Result<(), &'static str> {
+            seed.fill(7);
+            Ok(())
+        }
+        // Stands in for the NSM: the document is the fields it attests to.
+        fn mock_nsm(public_key: &[u8], user_data: Option<&[u8]>) -> Result<Vec<u8>, EnclaveError> {
+            Ok([public_key, user_data.unwrap_or_default()].concat())
+        }
+
+        crypto::ensure_initialized_with(crypto::SchemeKind::Ed25519, fixed_seed).unwrap();
+        let signing_key = crypto::public_key_bytes().unwrap();
+        let document = attest_keys(b"ephemeral", mock_nsm).unwrap();
+        assert!(document.starts_with(b"ephemeral"));
+        assert!(document
+            .windows(signing_key.len())
+            .any(|window| window == signing_key));
+    }
+
+    #[test]
+    fn listen_addr_parses_ipv4_and_ipv6() {
+        assert_eq!(
+            listen_addr("0.0.0.0", "3100").unwrap(),
+            "0.0.0.0:3100".parse().unwrap()
+        );
+        assert_eq!(
+            listen_addr(" ::1 ", "8080").unwrap(),
+            "[::1]:8080".parse().unwrap()
+        );
+        assert!(listen_addr("localhost", "3100")
+            .unwrap_err()
+            .starts_with("BIND_ADDR"));
+        assert!(listen_addr("127.0.0.1", "65536")
+            .unwrap_err()
+            .starts_with("PORT"));
+    }
+
+    #[test]
+    fn api_key_matches_only_itself() {
+        assert!(verify_api_key("s3cret", "s3cret"));
+        assert!(!verify_api_key("s3cre", "s3cret"));
+        assert!(!verify_api_key("s3cret ", "s3cret"));
+        assert!(!verify_api_key("", "s3cret"));
+        assert!(verify_api_key("", ""));
+    }
+
+    #[cfg(all(feature = "orders", not(feature = "nitro")))]
+    #[tokio::test]
+    async fn health_outside_an_enclave_reports_null_pcrs() {
+        use axum::body::{to_bytes, Body};
+        use axum::http::{Request, StatusCode};
+        use tower::ServiceExt;
+
+        let app = crate::build_router(AppState::for_tests(Default::default()));
+        let response = app
+            .oneshot(Request::get("/health_check").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert!(body["pcrs"].is_null(), "{}", body);
+        assert!(body["pk"].is_string());
+    }
+}
@@ -0,0 +1,107 @@
+// Copyright (c), Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::EnclaveError;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Header on `/get_attestation` giving the served document's age in ms.
+pub const ATTESTATION_AGE_HEADER: &str = "x-attestation-age-ms";
+
+struct Cached {
+    /// The key material the document commits to.
+    key: Vec<u8>,
+    document: Vec<u8>,
+    created: Instant,
+}
+
+/// The last attestation document, reused for `ttl` while the keys it
+/// commits to are unchanged. Asking the NSM for a document is slow, and a
+/// fresh one every call lets any client load the enclave for free.
+pub struct AttestationCache {
+    ttl: Duration,
+    cached: Mutex<Option<Cached>>,
+}
+
+impl AttestationCache {
+    /// `ttl_ms == 0` disables caching.
+    pub fn new(ttl_ms: u64) -> Self {
+        Self {
+            ttl: Duration::from_millis(ttl_ms),
+            cached: Mutex::new(None),
+        }
+    }
+
+    /// The document for `key` and its age, from the cache while it is fresh
+    /// and for the same key, otherwise from `produce`. Concurrent callers
+    /// wait for a single refresh rather than each asking the NSM.
+    pub fn get_or_refresh(
+        &self,
+        key: Vec<u8>,
+        produce: impl FnOnce() -> Result<Vec<u8>, EnclaveError>,
+    ) -> Result<(Vec<u8>, Duration), EnclaveError> {
+        let mut cached = self.cached.lock().expect("attestation cache lock poisoned");
+        if let Some(entry) = cached.as_ref() {
+            let age = entry.created.elapsed();
+            if entry.key == key && age < self.ttl {
+                return Ok((entry.document.clone(), age));
+            }
+        }
+        let document = produce()?;
+        *cached = Some(Cached {
+            key,
+            document: document.clone(),
+            created: Instant::now(),
+        });
+        Ok((document, Duration::ZERO))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    #[test]
+    fn rapid_calls_share_a_document_until_the_ttl() {
+        let cache = AttestationCache::new(50);
+        let produced = Cell::new(0u8);
+        let produce = || {
+            produced.set(produced.get() + 1);
+            Ok(vec![produced.get()])
+        };
+
+        let (first, age) = cache.get_or_refresh(b"key".to_vec(), produce).unwrap();
+        assert_eq!(age, Duration::ZERO);
+        let (second, _) = cache.get_or_refresh(b"key".to_vec(), produce).unwrap();
+        assert_eq!((first, produced.get()), (second, 1));
+
+        std::thread::sleep(Duration::from_millis(60));
+        let (refreshed, age) = cache.get_or_refresh(b"key".to_vec(), produce).unwrap();
+        assert_eq!(
+            (refreshed, age, produced.get()),
+            (vec![2], Duration::ZERO, 2)
+        );
+    }
+
+    #[test]
+    fn a_new_key_or_zero_ttl_always_refreshes() {
+        let cache = AttestationCache::new(60_000);
+        cache
+            .get_or_refresh(b"old".to_vec(), || Ok(vec![1]))
+            .unwrap();
+        let (document, _) = cache
+            .get_or_refresh(b"new".to_vec(), || Ok(vec![2]))
+            .unwrap();
+        assert_eq!(document, vec![2]);
+
+        let uncached = AttestationCache::new(0);
+        uncached
+            .get_or_refresh(b"key".to_vec(), || Ok(vec![1]))
+            .unwrap();
+        let (document, _) = uncached
+            .get_or_refresh(b"key".to_vec(), || Ok(vec![2]))
+            .unwrap();
+        assert_eq!(document, vec![2]);
+    }
+}
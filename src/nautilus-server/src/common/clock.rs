@@ -0,0 +1,75 @@
+// Copyright (c), Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use tracing::warn;
+
+/// Last `server_timestamp_ms` handed out. Wall-clock readings are clamped
+/// against it so signed timestamps keep increasing even if NTP steps the
+/// clock backwards.
+static LAST_TIMESTAMP_MS: AtomicU64 = AtomicU64::new(0);
+
+/// Current time for `server_timestamp_ms`: wall clock in ms since the Unix
+/// epoch, but never earlier than or equal to a value already returned in
+/// this process.
+pub fn unix_time_ms() -> u64 {
+    next_timestamp_ms(wall_clock_ms())
+}
+
+/// Return `now`, or one past the previously issued timestamp if the clock
+/// has not moved forward since, so no two responses share a timestamp.
+fn next_timestamp_ms(now: u64) -> u64 {
+    let prev = LAST_TIMESTAMP_MS
+        .fetch_update(Ordering::AcqRel, Ordering::Acquire, |last| {
+            Some(now.max(last + 1))
+        })
+        .unwrap_or_else(|last| last);
+    if now < prev {
+        warn!(
+            wall_clock_ms = now,
+            last_issued_ms = prev,
+            "Wall clock behind last issued timestamp; clamping forward"
+        );
+    }
+    now.max(prev + 1)
+}
+
+fn wall_clock_ms() -> u64 {
+    // Available with std; enclave has /dev/rtc/time source. Replace if monotonic-only.
+    use std::time::{SystemTime, UNIX_EPOCH};
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backward_clock_jump_keeps_timestamps_increasing() {
+        let now = wall_clock_ms() + 1_000;
+        let before = next_timestamp_ms(now);
+        let after = next_timestamp_ms(now - 5_000);
+        assert!(after > before);
+        assert!(next_timestamp_ms(now - 10_000) > after);
+    }
+
+    #[test]
+    fn concurrent_callers_get_distinct_increasing_timestamps() {
+        let threads: Vec<_> = (0..4)
+            .map(|_| std::thread::spawn(|| (0..250).map(|_| unix_time_ms()).collect::<Vec<_>>()))
+            .collect();
+        let mut all = Vec::new();
+        for thread in threads {
+            let issued = thread.join().unwrap();
+            assert!(issued.windows(2).all(|pair| pair[0] < pair[1]));
+            all.extend(issued);
+        }
+        let count = all.len();
+        all.sort_unstable();
+        all.dedup();
+        assert_eq!(all.len(), count, "a timestamp was issued twice");
+    }
+}
@@ -0,0 +1,241 @@
+// Copyright (c), Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use axum::http::{HeaderName, HeaderValue};
+use std::fmt;
+use std::net::SocketAddr;
+use std::str::FromStr;
+
+use crate::request_id::DEFAULT_REQUEST_ID_HEADER;
+
+const DEFAULT_BIND_ADDR: &str = "0.0.0.0";
+const DEFAULT_PORT: &str = "3100";
+const DEFAULT_ATTESTATION_CACHE_MS: u64 = 60_000;
+const DEFAULT_SHUTDOWN_DRAIN_MS: u64 = 30_000;
+
+/// Every bad setting found while loading configuration, reported together.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigError(pub Vec<String>);
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid configuration: {}", self.0.join("; "))
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl ConfigError {
+    /// Both loaded settings, or one error listing what either got wrong.
+    pub fn combine<A, B>(
+        a: Result<A, ConfigError>,
+        b: Result<B, ConfigError>,
+    ) -> Result<(A, B), ConfigError> {
+        match (a, b) {
+            (Ok(a), Ok(b)) => Ok((a, b)),
+            (a, b) => Err(ConfigError(
+                a.err()
+                    .into_iter()
+                    .chain(b.err())
+                    .flat_map(|e| e.0)
+                    .collect(),
+            )),
+        }
+    }
+}
+
+/// Server-wide settings, read once from the environment at boot and kept in
+/// `AppState`. Order-processing settings live in `orders::OrdersConfig`.
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// Socket to listen on, from `BIND_ADDR` and `PORT`.
+    pub listen_addr: SocketAddr,
+    /// Origins allowed by CORS, from `CORS_ALLOWED_ORIGINS`. `None` when
+    /// unset, which allows any origin.
+    pub cors_origins: Option<Vec<HeaderValue>>,
+    /// Header carrying the request id, from `REQUEST_ID_HEADER`.
+    pub request_id_header: HeaderName,
+    /// How long `/get_attestation` reuses a document, from
+    /// `ATTESTATION_CACHE_MS`. 0 disables the cache.
+    pub attestation_cache_ms: u64,
+    /// How long in-flight requests get to finish after a shutdown signal,
+    /// from `NAUTILUS_SHUTDOWN_DRAIN_MS`.
+    pub shutdown_drain_ms: u64,
+    /// `API_KEY`; required outside orders builds, unused in them.
+    pub api_key: String,
+}
+
+impl Config {
+    pub fn from_env() -> Result<Self, ConfigError> {
+        Self::from_lookup(|name| std::env::var(name).ok())
+    }
+
+    /// Load from `lookup`, checking every setting before giving up so a
+    /// misconfigured deployment learns about all of its mistakes at once.
+    pub fn from_lookup(lookup: impl Fn(&str) -> Option<String>) -> Result<Self, ConfigError> {
+        let mut errors = Vec::new();
+
+        let listen_addr = super::listen_addr(
+            &lookup("BIND_ADDR").unwrap_or_else(|| DEFAULT_BIND_ADDR.to_string()),
+            &lookup("PORT").unwrap_or_else(|| DEFAULT_PORT.to_string()),
+        )
+        .map_err(|e| errors.push(e))
+        .ok();
+
+        let cors_origins = match lookup("CORS_ALLOWED_ORIGINS") {
+            Some(raw) => parse_origins(&raw)
+                .map(Some)
+                .map_err(|e| errors.push(e))
+                .ok(),
+            None => Some(None),
+        };
+
+        let request_id_name =
+            lookup("REQUEST_ID_HEADER").unwrap_or_else(|| DEFAULT_REQUEST_ID_HEADER.to_string());
+        let request_id_header = HeaderName::from_bytes(request_id_name.trim().as_bytes())
+            .map_err(|_| {
+                errors.push(format!(
+                    "REQUEST_ID_HEADER {:?} is not a valid header name",
+                    request_id_name
+                ))
+            })
+            .ok();
+
+        let attestation_cache_ms = parse_or(
+            &lookup,
+            "ATTESTATION_CACHE_MS",
+            DEFAULT_ATTESTATION_CACHE_MS,
+            &mut errors,
+        );
+        let shutdown_drain_ms = parse_or(
+            &lookup,
+            "NAUTILUS_SHUTDOWN_DRAIN_MS",
+            DEFAULT_SHUTDOWN_DRAIN_MS,
+            &mut errors,
+        );
+
+        let api_key = lookup("API_KEY").unwrap_or_default();
+        if cfg!(not(feature = "orders")) && api_key.is_empty() {
+            errors.push("API_KEY must be set".to_string());
+        }
+
+        match (listen_addr, cors_origins, request_id_header) {
+            (Some(listen_addr), Some(cors_origins), Some(request_id_header))
+                if errors.is_empty() =>
+            {
+                Ok(Self {
+                    listen_addr,
+                    cors_origins,
+                    request_id_header,
+                    attestation_cache_ms,
+                    shutdown_drain_ms,
+                    api_key,
+                })
+            }
+            _ => Err(ConfigError(errors)),
+        }
+    }
+}
+
+/// `name` parsed as a `T`, or `default` when unset. A value that does not
+/// parse is recorded in `errors` and replaced by `default`.
+pub(crate) fn parse_or<T: FromStr>(
+    lookup: &impl Fn(&str) -> Option<String>,
+    name: &str,
+    default: T,
+    errors: &mut Vec<String>,
+) -> T {
+    match lookup(name) {
+        Some(raw) => raw.trim().parse().unwrap_or_else(|_| {
+            errors.push(format!("{} has an invalid value: {:?}", name, raw));
+            default
+        }),
+        None => default,
+    }
+}
+
+/// Every entry of a comma-separated `CORS_ALLOWED_ORIGINS`, reporting all
+/// malformed entries rather than just the first.
+fn parse_origins(raw: &str) -> Result<Vec<HeaderValue>, String> {
+    let (origins, errors): (Vec<_>, Vec<_>) = raw
+        .split(',')
+        .map(str::trim)
+        .filter(|origin| !origin.is_empty())
+        .map(super::parse_origin)
+        .partition(Result::is_ok);
+    if !errors.is_empty() {
+        return Err(errors
+            .into_iter()
+            .filter_map(Result::err)
+            .collect::<Vec<_>>()
+            .join("; "));
+    }
+    if origins.is_empty() {
+        return Err("CORS_ALLOWED_ORIGINS is set but lists no origins".to_string());
+    }
+    Ok(origins.into_iter().filter_map(Result::ok).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn lookup(vars: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        move |name| vars.get(name).cloned()
+    }
+
+    #[test]
+    fn defaults_apply_when_unset() {
+        let config = Config::from_lookup(lookup(&[("API_KEY", "k")])).unwrap();
+        assert_eq!(config.listen_addr.port(), 3100);
+        assert!(config.cors_origins.is_none());
+        assert_eq!(config.attestation_cache_ms, DEFAULT_ATTESTATION_CACHE_MS);
+        assert_eq!(config.shutdown_drain_ms, DEFAULT_SHUTDOWN_DRAIN_MS);
+    }
+
+    #[test]
+    fn every_bad_setting_is_reported_in_one_pass() {
+        let err = Config::from_lookup(lookup(&[
+            ("API_KEY", "k"),
+            ("PORT", "99999"),
+            (
+                "CORS_ALLOWED_ORIGINS",
+                "https://ok.example, ftp://bad.example",
+            ),
+        ]))
+        .unwrap_err();
+        assert_eq!(err.0.len(), 2, "{}", err);
+        assert!(err.0[0].starts_with("PORT"));
+        assert!(err.0[1].contains("ftp://bad.example"));
+        assert!(err.to_string().starts_with("invalid configuration: "));
+    }
+
+    #[test]
+    fn allowlisted_origins_are_kept() {
+        let config = Config::from_lookup(lookup(&[
+            ("API_KEY", "k"),
+            (
+                "CORS_ALLOWED_ORIGINS",
+                "https://a.example,http://b.example:8080",
+            ),
+        ]))
+        .unwrap();
+        assert_eq!(config.cors_origins.unwrap().len(), 2);
+    }
+
+    #[test]
+    fn combine_merges_both_reports() {
+        let a: Result<(), _> = Err(ConfigError(vec!["a".to_string()]));
+        let b: Result<(), _> = Err(ConfigError(vec!["b".to_string()]));
+        assert_eq!(
+            ConfigError::combine(a, b).unwrap_err().0,
+            vec!["a".to_string(), "b".to_string()]
+        );
+        assert_eq!(ConfigError::combine(Ok(1), Ok(2)), Ok((1, 2)));
+    }
+}
@@ -8,42 +8,277 @@ use axum::Json;
 use fastcrypto::ed25519::Ed25519KeyPair;
 use serde_json::json;
 use std::fmt;
+#[cfg(feature = "orders")]
+use std::sync::Arc;
+#[cfg(feature = "orders")]
+use std::time::Duration;
+use tower_http::cors::{Any, CorsLayer};
+#[cfg(feature = "orders")]
+use tower_http::limit::RequestBodyLimitLayer;
+#[cfg(feature = "orders")]
+use tower_http::timeout::TimeoutLayer;
 
 // Only include orders module (directly in src/, not in apps/)
 #[cfg(feature = "orders")]
 pub mod orders {
+    pub mod admin;
+    pub mod audit;
+    pub mod config;
+    pub mod cose;
+    pub mod cosign;
     pub mod crypto;
+    pub mod health;
+    pub mod idempotency;
+    pub mod kms;
+    pub mod limits;
+    pub mod metadata;
+    pub mod money;
+    pub mod naming;
+    pub mod negotiate;
     pub mod order;
+    pub mod routes;
+    pub mod store;
+    pub mod verification;
+    pub mod webhook;
 
+    pub use config::OrdersConfig;
     pub use crypto::{ensure_initialized, public_key_base64, sign};
     pub use order::{
-        make_response, sign_response, sign_response_with_v2, OrderAction, OrderRequest,
-        OrderStatus, OrderV2Fields, SignableOrderResponse, SignedOrderResponse,
+        make_response, process_order, sign_response, sign_response_with_v2, signing_message,
+        OrderAction, OrderRequest, OrderStatus, OrderV2Fields, SignableOrderResponse,
+        SignedOrderResponse, SigningFormat,
     };
 }
 
 pub mod common;
+pub mod metrics;
+pub mod request_id;
+
+/// Permissive fallback used by `common::build_cors` when no allowlist is
+/// configured.
+pub fn cors_layer() -> CorsLayer {
+    CorsLayer::new()
+        .allow_methods(Any)
+        .allow_headers(Any)
+        .allow_origin(Any)
+}
+
+/// Every order route with its middleware applied. `main` serves this
+/// directly; embedders can mount it in a larger app with `Router::nest`.
+/// As in `main`, install the signing key and run `orders::crypto::warm_up`
+/// first, or `/ready` stays unhealthy. CORS and the request id header come
+/// from `state.config`.
+#[cfg(feature = "orders")]
+pub fn build_router(state: Arc<AppState>) -> axum::Router {
+    use axum::routing::{get, post};
+    use common::{get_attestation, health_check, ping};
+    use orders::routes;
+
+    let cors = common::build_cors(state.config.cors_origins.as_deref());
+    let request_id_header = state.config.request_id_header.clone();
+
+    // Routes that produce signatures sit behind the optional API key.
+    let signing = axum::Router::new()
+        .route("/orders/process", post(routes::process_order_http))
+        .route("/orders/process_batch", post(routes::process_batch_http))
+        .route_layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            orders::admin::require_api_key,
+        ));
+
+    let router = axum::Router::new()
+        .route("/", get(ping))
+        .route("/get_attestation", get(get_attestation))
+        .route("/health_check", get(health_check))
+        .route("/metrics", get(metrics::metrics_handler))
+        .merge(signing)
+        .route("/orders/health", get(routes::orders_health))
+        .route("/orders/jwk", get(routes::orders_jwk))
+        .route("/orders/pubkey", get(routes::orders_pubkey))
+        .route("/orders/verify", post(routes::verify_order))
+        .route("/orders/preview", post(routes::preview_order))
+        .route("/orders/recent", get(routes::recent_orders))
+        .route("/orders/:order_id", get(routes::get_order))
+        .route("/ready", get(routes::ready))
+        .route("/admin/checkin", post(routes::admin_checkin))
+        .route("/admin/rotate_key", post(routes::admin_rotate_key))
+        .with_state(state.clone());
+
+    with_request_limits(router, &state.orders_config)
+        .layer(cors)
+        .layer(axum::middleware::from_fn(
+            move |req: axum::extract::Request, next: axum::middleware::Next| {
+                request_id::propagate(request_id_header.clone(), req, next)
+            },
+        ))
+}
+
+/// `router` behind the body size limit, request timeout and concurrency
+/// cap from `config`.
+#[cfg(feature = "orders")]
+fn with_request_limits(router: axum::Router, config: &orders::OrdersConfig) -> axum::Router {
+    use axum::extract::DefaultBodyLimit;
+
+    router
+        // Replaces axum's 2 MiB extractor default so the configured limit
+        // is the only one; oversized bodies are refused with 413.
+        .layer(DefaultBodyLimit::disable())
+        .layer(RequestBodyLimitLayer::new(config.max_body_bytes.get()))
+        // Outside the body limit and API key checks, so a hung handler or a
+        // slow upload is cut off with 504 whichever layer it is stuck in.
+        .layer(TimeoutLayer::with_status_code(
+            StatusCode::GATEWAY_TIMEOUT,
+            Duration::from_millis(config.request_timeout_ms),
+        ))
+        // Outside the timeout, so a shed request is answered at once instead
+        // of waiting out its timer. `Router::layer` wraps each route
+        // separately, so the limit must be the global one to be shared.
+        .layer(
+            tower::ServiceBuilder::new()
+                .layer(axum::error_handling::HandleErrorLayer::new(
+                    |_: tower::BoxError| async {
+                        EnclaveError::Unavailable {
+                            code: "overloaded",
+                            message: "too many concurrent requests, retry later".to_string(),
+                        }
+                    },
+                ))
+                .layer(tower::load_shed::LoadShedLayer::new())
+                .layer(tower::limit::GlobalConcurrencyLimitLayer::new(
+                    config.max_concurrent_requests.get(),
+                )),
+        )
+}
 
 /// App state, at minimum needs to maintain the ephemeral keypair.  
 pub struct AppState {
     /// Ephemeral keypair on boot
     pub eph_kp: Ed25519KeyPair,
-    /// API key (not used in orders mode, but kept for compatibility)
-    pub api_key: String,
+    /// Server-wide configuration loaded at boot.
+    pub config: common::config::Config,
+    /// Last attestation document, reused by `/get_attestation` for a while.
+    pub attestation_cache: common::attestation_cache::AttestationCache,
+    /// Order-processing configuration loaded at boot.
+    #[cfg(feature = "orders")]
+    pub orders_config: orders::OrdersConfig,
+    /// Rolling per-customer spend tracked against `orders_config` caps.
+    #[cfg(feature = "orders")]
+    pub spending_windows: orders::limits::SpendingWindows,
+    /// Stops signing when monitoring stops checking in.
+    #[cfg(feature = "orders")]
+    pub dead_man: orders::admin::DeadManSwitch,
+    /// Last `sequence` stamped on a signed response. In memory only, so it
+    /// resets on restart.
+    #[cfg(feature = "orders")]
+    pub sequence: std::sync::atomic::AtomicU64,
+    /// Per-order status for the escrow state machine, action count and the
+    /// last signed response.
+    #[cfg(feature = "orders")]
+    pub order_store: orders::store::OrderStore,
+    /// `(order_id, action)` pairs already settled, to refuse replays.
+    #[cfg(feature = "orders")]
+    pub replay_guard: orders::limits::ReplayGuard,
+    /// Operator co-signer, when dual control is configured.
+    #[cfg(feature = "orders")]
+    pub operator_cosigner: Option<orders::cosign::OperatorCosigner>,
+    /// Responses cached by `Idempotency-Key` for retried requests.
+    #[cfg(feature = "orders")]
+    pub idempotency: orders::idempotency::IdempotencyCache,
+    /// Pushes signed responses to `WEBHOOK_URL`, when configured.
+    #[cfg(feature = "orders")]
+    pub webhook: Option<orders::webhook::Webhook>,
+    /// Audit trail of signed responses, when `AUDIT_LOG_PATH` is set.
+    #[cfg(feature = "orders")]
+    pub audit_log: Option<orders::audit::AuditLog>,
+}
+
+#[cfg(all(test, feature = "orders"))]
+impl AppState {
+    /// State as `main` builds it for `orders_config`, with default server
+    /// settings and no co-signer, webhook or audit log.
+    pub(crate) fn for_tests(orders_config: orders::OrdersConfig) -> Arc<Self> {
+        use fastcrypto::traits::KeyPair;
+        Arc::new(AppState {
+            eph_kp: Ed25519KeyPair::generate(&mut rand::thread_rng()),
+            config: common::config::Config::from_lookup(|_| None).unwrap(),
+            attestation_cache: common::attestation_cache::AttestationCache::new(0),
+            spending_windows: orders::limits::SpendingWindows::new(
+                orders_config.customer_window_ms,
+                orders_config.customer_caps.clone(),
+            ),
+            dead_man: orders::admin::DeadManSwitch::new(orders_config.dead_man_window_ms),
+            sequence: std::sync::atomic::AtomicU64::new(0),
+            order_store: orders::store::OrderStore::new(orders_config.order_store_capacity),
+            replay_guard: orders::limits::ReplayGuard::new(orders_config.replay_guard_capacity),
+            operator_cosigner: None,
+            idempotency: orders::idempotency::IdempotencyCache::new(
+                orders_config.idempotency_ttl_ms,
+            ),
+            webhook: None,
+            audit_log: None,
+            orders_config,
+        })
+    }
 }
 
 /// Implement IntoResponse for EnclaveError.
-impl IntoResponse for EnclaveError {
-    fn into_response(self) -> Response {
-        let (status, error_message) = match self {
-            EnclaveError::GenericError(msg) => (StatusCode::INTERNAL_SERVER_ERROR, msg),
+impl EnclaveError {
+    /// HTTP status and the `{"code", "message", "details"}` object for this
+    /// error. `code` is stable and machine-readable; `details` may be empty.
+    pub fn into_status_and_body(self) -> (StatusCode, serde_json::Value) {
+        let (status, code, message, details) = match self {
+            EnclaveError::GenericError(msg) => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "internal_error",
+                msg,
+                vec![],
+            ),
+            EnclaveError::InvalidRequest { code, message } => {
+                (StatusCode::BAD_REQUEST, code, message, vec![])
+            }
+            EnclaveError::ValidationFailed(fields) => (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                "validation_failed",
+                "validation failed".to_string(),
+                fields,
+            ),
+            EnclaveError::TooLarge { code, message } => {
+                (StatusCode::PAYLOAD_TOO_LARGE, code, message, vec![])
+            }
+            EnclaveError::NotFound(msg) => (StatusCode::NOT_FOUND, "not_found", msg, vec![]),
+            EnclaveError::Conflict { code, message } => {
+                (StatusCode::CONFLICT, code, message, vec![])
+            }
+            EnclaveError::Unauthorized(msg) => {
+                (StatusCode::UNAUTHORIZED, "unauthorized", msg, vec![])
+            }
+            EnclaveError::Unavailable { code, message } => {
+                (StatusCode::SERVICE_UNAVAILABLE, code, message, vec![])
+            }
+            EnclaveError::UnsupportedMediaType(msg) => (
+                StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                "unsupported_media_type",
+                msg,
+                vec![],
+            ),
+            EnclaveError::KeyUnavailable(reason) => (
+                StatusCode::SERVICE_UNAVAILABLE,
+                "key_unavailable",
+                "enclave signing key is not available".to_string(),
+                vec![reason.to_string()],
+            ),
         };
 
-        let body = Json(json!({
-            "error": error_message,
-        }));
+        let body = json!({ "code": code, "message": message, "details": details });
+        (status, body)
+    }
+}
 
-        (status, body).into_response()
+/// Error bodies are `{"error": {"code": ..., "message": ..., "details": [...]}}`.
+impl IntoResponse for EnclaveError {
+    fn into_response(self) -> Response {
+        let (status, error) = self.into_status_and_body();
+        (status, Json(json!({ "error": error }))).into_response()
     }
 }
 
@@ -51,14 +286,379 @@ impl IntoResponse for EnclaveError {
 #[derive(Debug)]
 pub enum EnclaveError {
     GenericError(String),
+    /// Request rejected before signing. `code` is a stable machine-readable
+    /// identifier clients can branch on.
+    InvalidRequest {
+        code: &'static str,
+        message: String,
+    },
+    /// Request fields failed validation; one `"field: reason"` per entry.
+    ValidationFailed(Vec<String>),
+    /// Request exceeds a size limit, e.g. too many orders in a batch.
+    TooLarge {
+        code: &'static str,
+        message: String,
+    },
+    /// The requested resource does not exist.
+    NotFound(String),
+    /// Request clashes with work already done, e.g. a replayed settlement.
+    Conflict {
+        code: &'static str,
+        message: String,
+    },
+    /// Missing or wrong credentials for a protected route.
+    Unauthorized(String),
+    /// The enclave is up but refusing this work for now.
+    Unavailable {
+        code: &'static str,
+        message: String,
+    },
+    /// Request body in a content type the route does not accept.
+    UnsupportedMediaType(String),
+    /// No signing key is installed yet; the payload is the key-operation
+    /// error, e.g. `signing_key_uninitialized`.
+    KeyUnavailable(&'static str),
 }
 
 impl fmt::Display for EnclaveError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             EnclaveError::GenericError(msg) => write!(f, "Enclave error: {}", msg),
+            EnclaveError::InvalidRequest { code, message } => {
+                write!(f, "Invalid request ({}): {}", code, message)
+            }
+            EnclaveError::ValidationFailed(fields) => {
+                write!(f, "Validation failed: {}", fields.join("; "))
+            }
+            EnclaveError::TooLarge { code, message } => {
+                write!(f, "Too large ({}): {}", code, message)
+            }
+            EnclaveError::NotFound(msg) => write!(f, "Not found: {}", msg),
+            EnclaveError::Conflict { code, message } => {
+                write!(f, "Conflict ({}): {}", code, message)
+            }
+            EnclaveError::Unauthorized(msg) => write!(f, "Unauthorized: {}", msg),
+            EnclaveError::Unavailable { code, message } => {
+                write!(f, "Unavailable ({}): {}", code, message)
+            }
+            EnclaveError::UnsupportedMediaType(msg) => {
+                write!(f, "Unsupported media type: {}", msg)
+            }
+            EnclaveError::KeyUnavailable(reason) => {
+                write!(f, "Signing key unavailable: {}", reason)
+            }
         }
     }
 }
 
 impl std::error::Error for EnclaveError {}
+
+impl From<anyhow::Error> for EnclaveError {
+    fn from(err: anyhow::Error) -> Self {
+        EnclaveError::GenericError(format!("{:#}", err))
+    }
+}
+
+/// Malformed JSON can only come from a client, so syntax and truncation
+/// errors are a 422; anything else (a value that fails to serialize, I/O)
+/// is our fault and a 500.
+impl From<serde_json::Error> for EnclaveError {
+    fn from(err: serde_json::Error) -> Self {
+        use serde_json::error::Category;
+        match err.classify() {
+            Category::Syntax | Category::Eof => {
+                EnclaveError::ValidationFailed(vec![format!("body: {}", err)])
+            }
+            Category::Data | Category::Io => EnclaveError::GenericError(format!("JSON: {}", err)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn each_error_variant_has_its_status_and_code() {
+        let message = || "m".to_string();
+        let cases = [
+            (
+                EnclaveError::GenericError(message()),
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "internal_error",
+            ),
+            (
+                EnclaveError::InvalidRequest {
+                    code: "bad_thing",
+                    message: message(),
+                },
+                StatusCode::BAD_REQUEST,
+                "bad_thing",
+            ),
+            (
+                EnclaveError::ValidationFailed(vec!["amount: must be positive".to_string()]),
+                StatusCode::UNPROCESSABLE_ENTITY,
+                "validation_failed",
+            ),
+            (
+                EnclaveError::TooLarge {
+                    code: "batch_too_large",
+                    message: message(),
+                },
+                StatusCode::PAYLOAD_TOO_LARGE,
+                "batch_too_large",
+            ),
+            (
+                EnclaveError::NotFound(message()),
+                StatusCode::NOT_FOUND,
+                "not_found",
+            ),
+            (
+                EnclaveError::Conflict {
+                    code: "replayed_action",
+                    message: message(),
+                },
+                StatusCode::CONFLICT,
+                "replayed_action",
+            ),
+            (
+                EnclaveError::Unauthorized(message()),
+                StatusCode::UNAUTHORIZED,
+                "unauthorized",
+            ),
+            (
+                EnclaveError::Unavailable {
+                    code: "maintenance",
+                    message: message(),
+                },
+                StatusCode::SERVICE_UNAVAILABLE,
+                "maintenance",
+            ),
+            (
+                EnclaveError::UnsupportedMediaType(message()),
+                StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                "unsupported_media_type",
+            ),
+            (
+                EnclaveError::KeyUnavailable("signing_key_uninitialized"),
+                StatusCode::SERVICE_UNAVAILABLE,
+                "key_unavailable",
+            ),
+        ];
+        for (error, status, code) in cases {
+            let (actual_status, body) = error.into_status_and_body();
+            assert_eq!((actual_status, body["code"].as_str()), (status, Some(code)));
+            assert!(body["message"].is_string());
+            assert!(body["details"].is_array());
+        }
+    }
+
+    #[tokio::test]
+    async fn error_body_nests_under_error() {
+        let response =
+            EnclaveError::ValidationFailed(vec!["currency: not ISO".to_string()]).into_response();
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(
+            body,
+            json!({
+                "error": {
+                    "code": "validation_failed",
+                    "message": "validation failed",
+                    "details": ["currency: not ISO"],
+                }
+            })
+        );
+    }
+
+    #[cfg(feature = "orders")]
+    fn fixed_seed(seed: &mut [u8; 32]) -> Result<(), &'static str> {
+        seed.fill(7);
+        Ok(())
+    }
+
+    #[cfg(feature = "orders")]
+    #[tokio::test]
+    async fn router_serves_when_mounted_under_a_prefix() {
+        use axum::body::{to_bytes, Body};
+        use axum::http::Request;
+        use tower::ServiceExt;
+
+        orders::crypto::ensure_initialized_with(orders::crypto::SchemeKind::Ed25519, fixed_seed)
+            .unwrap();
+        let app = axum::Router::new().nest(
+            "/api",
+            build_router(AppState::for_tests(Default::default())),
+        );
+        let get = |uri: &str| Request::get(uri).body(Body::empty()).unwrap();
+
+        let response = app.clone().oneshot(get("/api/orders/jwk")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let jwk: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(jwk, orders::crypto::public_key_jwk().unwrap());
+
+        let response = app.oneshot(get("/orders/jwk")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[cfg(feature = "orders")]
+    #[tokio::test]
+    async fn metrics_count_a_processed_order() {
+        use axum::body::{to_bytes, Body};
+        use axum::http::Request;
+        use tower::ServiceExt;
+
+        orders::crypto::ensure_initialized_with(orders::crypto::SchemeKind::Ed25519, fixed_seed)
+            .unwrap();
+        metrics::install();
+        let app = build_router(AppState::for_tests(Default::default()));
+        let order = json!({
+            "version": 1,
+            "order_id": "metrics-o1",
+            "customer": "alice",
+            "merchant": "acme",
+            "amount": 100,
+            "currency": "USD",
+            "action": "initiate",
+        });
+        let response = app
+            .clone()
+            .oneshot(
+                Request::post("/orders/process")
+                    .header("content-type", "application/json")
+                    .body(Body::from(order.to_string()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = app
+            .oneshot(Request::get("/metrics").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body = String::from_utf8(body.to_vec()).unwrap();
+        assert!(body.contains(metrics::ORDERS_PROCESSED), "{}", body);
+    }
+
+    #[cfg(feature = "orders")]
+    #[tokio::test]
+    async fn body_just_over_the_limit_is_413() {
+        use axum::body::Body;
+        use axum::http::Request;
+        use std::num::NonZeroUsize;
+        use tower::ServiceExt;
+
+        let limit = 1024;
+        let app = build_router(AppState::for_tests(orders::OrdersConfig {
+            max_body_bytes: NonZeroUsize::new(limit).unwrap(),
+            ..Default::default()
+        }));
+        let mut order = json!({
+            "version": 1,
+            "order_id": "o1",
+            "customer": "alice",
+            "merchant": "acme",
+            "amount": 100,
+            "currency": "USD",
+            "action": "initiate",
+            "metadata": { "note": "" },
+        });
+        let padding = limit + 1 - order.to_string().len();
+        order["metadata"]["note"] = json!("x".repeat(padding));
+        let body = order.to_string();
+        assert_eq!(body.len(), limit + 1);
+
+        for content_length in [Some(body.len()), None] {
+            let mut request =
+                Request::post("/orders/process").header("content-type", "application/json");
+            if let Some(len) = content_length {
+                request = request.header("content-length", len);
+            }
+            let response = app
+                .clone()
+                .oneshot(request.body(Body::from(body.clone())).unwrap())
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+        }
+    }
+
+    /// A router whose only route answers after `delay`, behind the same
+    /// limits as `build_router`.
+    #[cfg(feature = "orders")]
+    fn slow_router(delay: Duration, config: orders::OrdersConfig) -> axum::Router {
+        let router = axum::Router::new().route(
+            "/slow",
+            axum::routing::get(move || async move {
+                tokio::time::sleep(delay).await;
+                "done"
+            }),
+        );
+        with_request_limits(router, &config)
+    }
+
+    #[cfg(feature = "orders")]
+    #[tokio::test]
+    async fn slow_handler_is_cut_off_with_504() {
+        use axum::body::Body;
+        use axum::http::Request;
+        use tower::ServiceExt;
+
+        let app = slow_router(
+            Duration::from_secs(5),
+            orders::OrdersConfig {
+                request_timeout_ms: 50,
+                ..Default::default()
+            },
+        );
+        let response = app
+            .oneshot(Request::get("/slow").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::GATEWAY_TIMEOUT);
+    }
+
+    #[cfg(feature = "orders")]
+    #[tokio::test]
+    async fn requests_past_the_concurrency_cap_are_shed() {
+        use axum::body::Body;
+        use axum::http::Request;
+        use std::num::NonZeroUsize;
+        use tower::ServiceExt;
+
+        let app = slow_router(
+            Duration::from_millis(300),
+            orders::OrdersConfig {
+                max_concurrent_requests: NonZeroUsize::new(2).unwrap(),
+                ..Default::default()
+            },
+        );
+        let requests: Vec<_> = (0..8)
+            .map(|_| {
+                let request = Request::get("/slow").body(Body::empty()).unwrap();
+                tokio::spawn(app.clone().oneshot(request))
+            })
+            .collect();
+        let mut statuses = Vec::new();
+        for request in requests {
+            statuses.push(request.await.unwrap().unwrap().status());
+        }
+
+        assert!(statuses.contains(&StatusCode::OK), "{:?}", statuses);
+        assert!(
+            statuses.contains(&StatusCode::SERVICE_UNAVAILABLE),
+            "{:?}",
+            statuses
+        );
+        assert!(statuses
+            .iter()
+            .all(|s| [StatusCode::OK, StatusCode::SERVICE_UNAVAILABLE].contains(s)));
+    }
+}
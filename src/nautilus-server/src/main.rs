@@ -2,16 +2,23 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use anyhow::Result;
+#[cfg(not(feature = "orders"))]
 use axum::{
     routing::{get, post},
-    Json, Router,
+    Router,
 };
+#[cfg(feature = "orders")]
+use fastcrypto::traits::ToFromBytes;
 use fastcrypto::{ed25519::Ed25519KeyPair, traits::KeyPair};
-use nautilus_server::common::{get_attestation, health_check};
+#[cfg(feature = "orders")]
+use nautilus_server::common::{attestation_self_test, attestation_with_pubkey};
+#[cfg(not(feature = "orders"))]
+use nautilus_server::common::{get_attestation, health_check, ping};
 use nautilus_server::AppState;
 use std::sync::Arc;
-use tower_http::cors::{Any, CorsLayer};
-use tracing::info;
+use std::time::Duration;
+use tokio::sync::Notify;
+use tracing::{info, warn};
 
 #[cfg(not(feature = "orders"))]
 use nautilus_server::app::process_data;
@@ -21,11 +28,17 @@ use nautilus_server::orders;
 
 #[tokio::main]
 async fn main() -> Result<()> {
+    // RUST_LOG picks levels per module, e.g. `nautilus_server::orders=debug`;
+    // unset means `info` everywhere.
+    let env_filter = tracing_subscriber::EnvFilter::try_from_default_env()
+        .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new("info"));
+
     // ✅ Production-ready structured logging
     #[cfg(debug_assertions)]
     {
         // Development: human-readable logs
         tracing_subscriber::fmt()
+            .with_env_filter(env_filter)
             .with_target(false)
             .compact()
             .init();
@@ -36,6 +49,7 @@ async fn main() -> Result<()> {
         // Production: JSON logs for Railway/monitoring tools
         use tracing_subscriber::fmt::format::FmtSpan;
         tracing_subscriber::fmt()
+            .with_env_filter(env_filter)
             .with_span_events(FmtSpan::CLOSE)
             .json()
             .init();
@@ -43,32 +57,133 @@ async fn main() -> Result<()> {
 
     info!("🚀 Starting Nautilus Server...");
 
+    // API_KEY is only required outside orders mode; see `Config::api_key`.
+    let config = nautilus_server::common::config::Config::from_env();
+    // Both loaders are run before failing so every bad setting is reported
+    // in one go.
+    #[cfg(feature = "orders")]
+    let (config, orders_config) = nautilus_server::common::config::ConfigError::combine(
+        config,
+        orders::OrdersConfig::from_env(),
+    )?;
+    #[cfg(not(feature = "orders"))]
+    let config = config?;
+    let attestation_cache = nautilus_server::common::attestation_cache::AttestationCache::new(
+        config.attestation_cache_ms,
+    );
+    let addr = config.listen_addr;
+    // How long in-flight requests get to finish once a shutdown signal
+    // arrives, so a signed order is still returned to its client.
+    let drain = Duration::from_millis(config.shutdown_drain_ms);
+    #[cfg(not(feature = "orders"))]
+    let (cors, request_id_header) = (
+        nautilus_server::common::build_cors(config.cors_origins.as_deref()),
+        config.request_id_header.clone(),
+    );
+
     let eph_kp = Ed25519KeyPair::generate(&mut rand::thread_rng());
 
-    // In orders mode, don't require API_KEY at runtime.
     #[cfg(feature = "orders")]
-    let api_key = String::new();
+    orders::config::log_security_posture(&config, &orders_config);
 
-    // Otherwise require API_KEY to be set.
-    #[cfg(not(feature = "orders"))]
-    let api_key = std::env::var("API_KEY").expect("API_KEY must be set");
+    #[cfg(feature = "orders")]
+    let spending_windows = orders::limits::SpendingWindows::new(
+        orders_config.customer_window_ms,
+        orders_config.customer_caps.clone(),
+    );
+    #[cfg(feature = "orders")]
+    let dead_man = orders::admin::DeadManSwitch::new(orders_config.dead_man_window_ms);
+    #[cfg(feature = "orders")]
+    let replay_guard = orders::limits::ReplayGuard::new(orders_config.replay_guard_capacity);
+    #[cfg(feature = "orders")]
+    let operator_cosigner = orders::cosign::OperatorCosigner::from_config(&orders_config)
+        .map_err(anyhow::Error::msg)?;
+    #[cfg(feature = "orders")]
+    let webhook =
+        orders::webhook::Webhook::from_config(&orders_config).map_err(anyhow::Error::msg)?;
+    #[cfg(feature = "orders")]
+    let audit_log = match &orders_config.audit_log_path {
+        Some(path) => Some(
+            orders::audit::AuditLog::open(path)
+                .await
+                .map_err(|e| anyhow::anyhow!("cannot open audit log {}: {}", path, e))?,
+        ),
+        None => None,
+    };
+    #[cfg(feature = "orders")]
+    let idempotency_ttl_ms = orders_config.idempotency_ttl_ms;
+    #[cfg(feature = "orders")]
+    let order_store = orders::store::OrderStore::new(orders_config.order_store_capacity);
 
-    let state = Arc::new(AppState { eph_kp, api_key });
+    nautilus_server::metrics::install();
 
-    // Initialize signing key early. In production, replace with KMS-sealed key init.
+    let state = Arc::new(AppState {
+        eph_kp,
+        config,
+        attestation_cache,
+        #[cfg(feature = "orders")]
+        orders_config,
+        #[cfg(feature = "orders")]
+        spending_windows,
+        #[cfg(feature = "orders")]
+        dead_man,
+        #[cfg(feature = "orders")]
+        sequence: std::sync::atomic::AtomicU64::new(0),
+        #[cfg(feature = "orders")]
+        order_store,
+        #[cfg(feature = "orders")]
+        replay_guard,
+        #[cfg(feature = "orders")]
+        operator_cosigner,
+        #[cfg(feature = "orders")]
+        idempotency: orders::idempotency::IdempotencyCache::new(idempotency_ttl_ms),
+        #[cfg(feature = "orders")]
+        webhook,
+        #[cfg(feature = "orders")]
+        audit_log,
+    });
+
+    // Initialize signing key early. SIGNING_KEY_KMS_ARN or
+    // NAUTILUS_KEY_SOURCE=kms unseal the seed via attested kmstool decrypt;
+    // only with neither is an ephemeral key generated. KMS failures abort boot.
     #[cfg(feature = "orders")]
     {
         info!("🔐 Initializing enclave signing key...");
-        orders::crypto::ensure_initialized().expect("failed to initialize enclave signing key");
+        let config = &state.orders_config;
+        if config.key_from_kms() {
+            let ciphertext = config
+                .kms_sealed_seed
+                .as_deref()
+                .ok_or_else(|| anyhow::anyhow!("NAUTILUS_KMS_SEALED_SEED_B64 is not set"))?;
+            let kms = orders::kms::KmstoolCli {
+                key_id: config.kms_key_arn.clone(),
+                ..orders::kms::KmstoolCli::from_env(&config.kmstool_path, config.kms_proxy_port)
+                    .map_err(anyhow::Error::msg)?
+            };
+            orders::crypto::init_from_kms(config.signing_scheme, &kms, ciphertext)
+                .await
+                .map_err(|e| anyhow::anyhow!("KMS key initialization failed: {}", e))?;
+        }
+        orders::crypto::ensure_initialized(
+            config.signing_scheme,
+            config.signing_key_seed.as_ref().map(|seed| &seed.0),
+        )
+        .expect("failed to initialize enclave signing key");
         info!("✅ Enclave signing key initialized successfully");
-        info!(public_key = %orders::crypto::public_key_base64(), "Ed25519 public key");
-    }
+        let scheme = orders::crypto::active_scheme().map_err(anyhow::Error::msg)?;
+        let public_key = orders::crypto::public_key_base64().map_err(anyhow::Error::msg)?;
+        info!(scheme = scheme.as_str(), public_key = %public_key, "Signing public key");
+        if state.orders_config.per_merchant_keys {
+            orders::crypto::derive_merchant_keys(&state.orders_config.merchant_allowlist)
+                .map_err(anyhow::Error::msg)?;
+        }
+        orders::crypto::warm_up(state.orders_config.warmup_signs).map_err(anyhow::Error::msg)?;
 
-    // Define your own restricted CORS policy here if needed.
-    let cors = CorsLayer::new()
-        .allow_methods(Any)
-        .allow_headers(Any)
-        .allow_origin(Any);
+        attestation_self_test(state.orders_config.require_attestation_at_boot, || {
+            attestation_with_pubkey(state.eph_kp.public().as_bytes())
+        })
+        .map_err(|e| anyhow::anyhow!("attestation self-test failed: {}", e))?;
+    }
 
     #[cfg(not(feature = "orders"))]
     let app = Router::new()
@@ -76,80 +191,87 @@ async fn main() -> Result<()> {
         .route("/get_attestation", get(get_attestation))
         .route("/process_data", post(process_data))
         .route("/health_check", get(health_check))
+        .route("/metrics", get(nautilus_server::metrics::metrics_handler))
         .with_state(state)
-        .layer(cors);
+        .layer(cors)
+        .layer(axum::middleware::from_fn(
+            move |req: axum::extract::Request, next: axum::middleware::Next| {
+                nautilus_server::request_id::propagate(request_id_header.clone(), req, next)
+            },
+        ));
 
     #[cfg(feature = "orders")]
-    let app = Router::new()
-        .route("/", get(ping))
-        .route("/get_attestation", get(get_attestation))
-        .route("/health_check", get(health_check))
-        .route("/orders/process", post(process_order_http))
-        .route("/orders/health", get(orders_health))
-        .with_state(state)
-        .layer(cors);
-
-    // ✅ FIX: Read PORT from environment (Railway sets this dynamically)
-    let port = std::env::var("PORT")
-        .unwrap_or_else(|_| "3100".to_string())
-        .parse::<u16>()
-        .expect("PORT must be a valid number");
+    let app = nautilus_server::build_router(state);
 
-    let addr = format!("0.0.0.0:{}", port);
-
-    let listener = tokio::net::TcpListener::bind(&addr).await?;
+    let listener = tokio::net::TcpListener::bind(addr)
+        .await
+        .map_err(|e| anyhow::anyhow!("failed to bind {}: {}", addr, e))?;
     info!(addr = %listener.local_addr().unwrap(), "Server listening");
     info!("📋 Routes registered:");
     info!("  GET  /");
     info!("  GET  /get_attestation");
     info!("  GET  /health_check");
+    info!("  GET  /metrics");
     info!("  POST /orders/process");
+    info!("  POST /orders/process_batch");
     info!("  GET  /orders/health");
+    info!("  GET  /orders/jwk");
+    info!("  GET  /orders/pubkey");
+    info!("  POST /orders/verify");
+    info!("  POST /orders/preview");
+    info!("  GET  /orders/recent");
+    info!("  GET  /orders/:order_id");
+    info!("  GET  /ready");
+    info!("  POST /admin/checkin");
+    info!("  POST /admin/rotate_key");
     info!("🎯 Server ready to accept requests!");
 
-    axum::serve(listener, app.into_make_service())
-        .await
-        .map_err(|e| anyhow::anyhow!("Server error: {}", e))
-}
-
-async fn ping() -> &'static str {
-    info!("📍 Ping endpoint called");
-    "Pong!"
-}
-
-// HTTP handlers for orders feature
-#[cfg(feature = "orders")]
-async fn process_order_http(
-    Json(req): Json<orders::OrderRequest>,
-) -> Json<orders::SignedOrderResponse> {
-    info!(
-        order_id = %req.order_id,
-        action = ?req.action,
-        amount = req.amount,
-        currency = %req.currency,
-        "Processing order request"
-    );
-    let resp = orders::make_response(&req);
-    info!(order_id = %resp.order_id, status = ?resp.status, "Generated response");
-    // V2 shadow mode: when the request opts in, sign both V1 and V2 with the
-    // same enclave master key. Backend stores both signatures and verifies
-    // both independently. V2 fields default to None for backwards compat.
-    let signed = orders::sign_response_with_v2(&resp, req.v2.as_ref());
+    // Stop accepting connections on SIGTERM/SIGINT, then give in-flight
+    // requests up to `drain` to complete before exiting anyway.
+    let draining = Arc::new(Notify::new());
+    let server = axum::serve(listener, app.into_make_service()).with_graceful_shutdown({
+        let draining = draining.clone();
+        async move { draining.notified().await }
+    });
+    tokio::pin!(server);
+    tokio::select! {
+        res = &mut server => return res.map_err(|e| anyhow::anyhow!("Server error: {}", e)),
+        _ = shutdown_signal() => {}
+    }
     info!(
-        order_id = %signed.response.order_id,
-        public_key = %signed.public_key,
-        v2_signed = signed.signature_v2.is_some(),
-        "Signed response"
+        drain_ms = drain.as_millis() as u64,
+        "Shutdown signal received, draining connections"
     );
-    Json(signed)
+    draining.notify_one();
+    match tokio::time::timeout(drain, server).await {
+        Ok(res) => res.map_err(|e| anyhow::anyhow!("Server error: {}", e))?,
+        Err(_) => warn!("Drain period elapsed with requests still in flight"),
+    }
+    info!("Server stopped");
+    Ok(())
 }
 
-#[cfg(feature = "orders")]
-async fn orders_health() -> Json<serde_json::Value> {
-    let pk_b64 = orders::crypto::public_key_base64();
-    info!(public_key = %pk_b64, "Health check");
-    Json(serde_json::json!({
-        "status": "ok",
-        "ed25519_pubkey_b64": pk_b64
-    }))
+/// Resolves on Ctrl-C or, on unix, SIGTERM (what Railway sends on redeploy).
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("failed to install Ctrl-C handler");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("failed to install SIGTERM handler")
+            .recv()
+            .await;
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {}
+        _ = terminate => {}
+    }
 }
@@ -0,0 +1,49 @@
+// Copyright (c), Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use metrics_exporter_prometheus::{PrometheusBuilder, PrometheusHandle};
+use once_cell::sync::OnceCell;
+use std::time::Duration;
+
+/// Orders that produced a signed response, labelled by `action` and `status`.
+pub const ORDERS_PROCESSED: &str = "nautilus_orders_processed_total";
+/// Wall time spent producing the V1 (and optional V2) signatures.
+pub const SIGNING_LATENCY: &str = "nautilus_signing_latency_seconds";
+/// Orders currently tracked by the in-memory order store.
+pub const ORDER_STORE_SIZE: &str = "nautilus_order_store_size";
+/// Webhook notifications that were never delivered, labelled by `reason`.
+pub const WEBHOOK_FAILURES: &str = "nautilus_webhook_failures_total";
+
+static HANDLE: OnceCell<PrometheusHandle> = OnceCell::new();
+
+/// Install the process-wide Prometheus recorder. Idempotent, so `main` and
+/// embedders that build the router themselves can both call it.
+pub fn install() -> &'static PrometheusHandle {
+    HANDLE.get_or_init(|| {
+        PrometheusBuilder::new()
+            .install_recorder()
+            .expect("failed to install Prometheus recorder")
+    })
+}
+
+pub fn record_order(action: &'static str, status: &'static str) {
+    metrics::counter!(ORDERS_PROCESSED, "action" => action, "status" => status).increment(1);
+}
+
+pub fn record_signing_latency(elapsed: Duration) {
+    metrics::histogram!(SIGNING_LATENCY).record(elapsed.as_secs_f64());
+}
+
+pub fn set_order_store_size(len: usize) {
+    metrics::gauge!(ORDER_STORE_SIZE).set(len as f64);
+}
+
+pub fn record_webhook_failure(reason: &'static str) {
+    metrics::counter!(WEBHOOK_FAILURES, "reason" => reason).increment(1);
+}
+
+/// `GET /metrics`: Prometheus text exposition. Unauthenticated, like the
+/// other health endpoints; it carries counts only, no order data.
+pub async fn metrics_handler() -> String {
+    install().render()
+}
@@ -0,0 +1,144 @@
+// Copyright (c), Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::common::verify_api_key;
+use crate::{AppState, EnclaveError};
+use axum::extract::{Request, State};
+use axum::http::HeaderMap;
+use axum::middleware::Next;
+use axum::response::Response;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+use super::config::Secret;
+
+/// Header carrying the admin token on `/admin/*` routes.
+pub const ADMIN_TOKEN_HEADER: &str = "x-admin-token";
+
+/// Header carrying the shared API key on the order-signing routes.
+pub const API_KEY_HEADER: &str = "x-api-key";
+
+/// Whether `headers[name]` equals `expected`, via `verify_api_key`.
+fn header_matches(headers: &HeaderMap, name: &str, expected: &Secret) -> bool {
+    let provided = headers
+        .get(name)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default();
+    verify_api_key(provided, &expected.0)
+}
+
+/// Check the admin token header against the configured token. Admin routes
+/// are closed outright when no token is configured.
+pub fn require_admin(headers: &HeaderMap, expected: Option<&Secret>) -> Result<(), EnclaveError> {
+    let Some(expected) = expected else {
+        return Err(EnclaveError::Unauthorized(
+            "admin routes are disabled".to_string(),
+        ));
+    };
+    if !header_matches(headers, ADMIN_TOKEN_HEADER, expected) {
+        return Err(EnclaveError::Unauthorized(
+            "invalid admin token".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Middleware for the order-signing routes: when `ORDERS_API_KEY` is
+/// configured, refuse requests without a matching `x-api-key` header.
+/// A no-op otherwise.
+pub async fn require_api_key(
+    State(state): State<Arc<AppState>>,
+    request: Request,
+    next: Next,
+) -> Result<Response, EnclaveError> {
+    if let Some(expected) = &state.orders_config.orders_api_key {
+        if !header_matches(request.headers(), API_KEY_HEADER, expected) {
+            return Err(EnclaveError::Unauthorized(
+                "missing or invalid API key".to_string(),
+            ));
+        }
+    }
+    Ok(next.run(request).await)
+}
+
+/// Dead-man's switch: signing is only allowed while monitoring keeps
+/// checking in via `/admin/checkin`. If no check-in arrives within the
+/// window, order signing stops until the next one.
+pub struct DeadManSwitch {
+    window: Option<Duration>,
+    last_checkin: Mutex<Instant>,
+}
+
+impl DeadManSwitch {
+    /// `window_ms == 0` disables the switch. The clock starts at boot.
+    pub fn new(window_ms: u64) -> Self {
+        Self {
+            window: (window_ms > 0).then(|| Duration::from_millis(window_ms)),
+            last_checkin: Mutex::new(Instant::now()),
+        }
+    }
+
+    pub fn checkin(&self) {
+        *self.last_checkin.lock().expect("dead-man lock poisoned") = Instant::now();
+        info!("Monitoring check-in received");
+    }
+
+    /// Refuse with `maintenance` once the check-in window has lapsed.
+    pub fn ensure_armed(&self) -> Result<(), EnclaveError> {
+        let Some(window) = self.window else {
+            return Ok(());
+        };
+        let since = self
+            .last_checkin
+            .lock()
+            .expect("dead-man lock poisoned")
+            .elapsed();
+        if since > window {
+            warn!(
+                since_checkin_ms = since.as_millis() as u64,
+                "No monitoring check-in within window; signing disabled"
+            );
+            return Err(EnclaveError::Unavailable {
+                code: "maintenance",
+                message: "signing paused: monitoring has not checked in".to_string(),
+            });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Move the last check-in `by` into the past.
+    fn age(switch: &DeadManSwitch, by: Duration) {
+        let mut last = switch.last_checkin.lock().unwrap();
+        *last = last.checked_sub(by).unwrap();
+    }
+
+    #[test]
+    fn signing_is_disabled_past_the_checkin_window() {
+        let switch = DeadManSwitch::new(1_000);
+        switch.ensure_armed().unwrap();
+        age(&switch, Duration::from_millis(1_500));
+        let err = switch.ensure_armed().unwrap_err();
+        assert!(matches!(
+            err,
+            EnclaveError::Unavailable {
+                code: "maintenance",
+                ..
+            }
+        ));
+        switch.checkin();
+        switch.ensure_armed().unwrap();
+    }
+
+    #[test]
+    fn a_zero_window_disables_the_switch() {
+        let switch = DeadManSwitch::new(0);
+        age(&switch, Duration::from_secs(10));
+        switch.ensure_armed().unwrap();
+    }
+}
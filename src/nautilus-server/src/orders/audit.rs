@@ -0,0 +1,154 @@
+// Copyright (c), Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use serde_json::json;
+use tokio::fs::OpenOptions;
+use tokio::io::{AsyncWriteExt, BufWriter};
+use tokio::sync::mpsc;
+use tracing::{error, info};
+
+use super::health::HealthFlag;
+use super::SignedOrderResponse;
+
+/// Lines that may queue up behind a slow disk before new ones are dropped.
+const AUDIT_QUEUE_CAPACITY: usize = 4096;
+
+/// Append-only JSON-lines record of every signed response. Requests only
+/// enqueue a line; a background task owns the file and writes it out, so a
+/// slow disk never holds up signing. A line that cannot be queued or
+/// written is logged as an error, and flags the log degraded in `/ready`,
+/// while the order still succeeds.
+pub struct AuditLog {
+    tx: mpsc::Sender<String>,
+    health: HealthFlag,
+}
+
+impl AuditLog {
+    /// Open (or create) `path` for appending and start the writer task.
+    pub async fn open(path: &str) -> std::io::Result<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .await?;
+        let (tx, rx) = mpsc::channel(AUDIT_QUEUE_CAPACITY);
+        let health = HealthFlag::default();
+        tokio::spawn(write_lines(
+            BufWriter::new(file),
+            rx,
+            path.to_string(),
+            health.clone(),
+        ));
+        info!(path, "Audit log enabled");
+        Ok(Self { tx, health })
+    }
+
+    /// Whether lines are currently reaching the file.
+    pub fn health(&self) -> &HealthFlag {
+        &self.health
+    }
+
+    /// Queue one line for `signed`. Never waits.
+    pub fn record(&self, signed: &SignedOrderResponse) {
+        let line = json!({
+            "timestamp_ms": signed.response.server_timestamp_ms,
+            "order_id": signed.response.order_id,
+            "action": signed.response.action,
+            "status": signed.response.status,
+            "sequence": signed.response.sequence,
+            "signature": signed.signature,
+            "public_key": signed.public_key,
+        })
+        .to_string();
+        if let Err(e) = self.tx.try_send(line) {
+            self.health.fail();
+            error!(order_id = %signed.response.order_id, error = %e, "Audit line dropped");
+        }
+    }
+}
+
+/// Drain queued lines into the file, flushing whenever the queue runs dry.
+async fn write_lines(
+    mut writer: BufWriter<tokio::fs::File>,
+    mut rx: mpsc::Receiver<String>,
+    path: String,
+    health: HealthFlag,
+) {
+    while let Some(mut line) = rx.recv().await {
+        line.push('\n');
+        if let Err(e) = writer.write_all(line.as_bytes()).await {
+            health.fail();
+            error!(path = %path, error = %e, "Audit write failed");
+        }
+        if rx.is_empty() {
+            match writer.flush().await {
+                Ok(()) => health.recover(),
+                Err(e) => {
+                    health.fail();
+                    error!(path = %path, error = %e, "Audit flush failed");
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::orders::health::HealthStatus;
+    use crate::orders::{crypto, order};
+    use std::time::Duration;
+
+    fn fixed_seed(seed: &mut [u8; 32]) -> Result<(), &'static str> {
+        seed.fill(7);
+        Ok(())
+    }
+
+    fn signed(order_id: &str) -> SignedOrderResponse {
+        let req: order::OrderRequest = serde_json::from_value(json!({
+            "version": 1,
+            "order_id": order_id,
+            "customer": "alice",
+            "merchant": "acme",
+            "amount": 100,
+            "currency": "USD",
+            "action": "initiate",
+        }))
+        .unwrap();
+        let resp = order::process_order(&req, None, None, None, 60_000).unwrap();
+        order::sign_response(&resp).unwrap()
+    }
+
+    #[tokio::test]
+    async fn two_orders_append_two_lines() {
+        crypto::ensure_initialized_with(crypto::SchemeKind::Ed25519, fixed_seed).unwrap();
+        let path =
+            std::env::temp_dir().join(format!("nautilus-audit-{}.jsonl", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let log = AuditLog::open(path.to_str().unwrap()).await.unwrap();
+        log.record(&signed("o1"));
+        log.record(&signed("o2"));
+
+        // The writer task drains the queue in the background.
+        let mut lines = Vec::new();
+        for _ in 0..100 {
+            let contents = tokio::fs::read_to_string(&path).await.unwrap();
+            lines = contents.lines().map(str::to_string).collect::<Vec<_>>();
+            if lines.len() == 2 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(lines.len(), 2);
+        let order_ids: Vec<serde_json::Value> = lines
+            .iter()
+            .map(|line| {
+                serde_json::from_str::<serde_json::Value>(line).unwrap()["order_id"].clone()
+            })
+            .collect();
+        assert_eq!(order_ids, [json!("o1"), json!("o2")]);
+        assert_eq!(log.health().status(), HealthStatus::Healthy);
+    }
+}
@@ -0,0 +1,788 @@
+// Copyright (c), Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use base64::engine::general_purpose::STANDARD as B64;
+use base64::Engine;
+use std::collections::HashMap;
+use std::num::NonZeroUsize;
+use std::str::FromStr;
+use tracing::info;
+
+use super::crypto::SchemeKind;
+use super::limits::REPLAY_ENTRY_BYTES;
+use super::money::BasisPoints;
+use super::naming::FieldNaming;
+use super::order::SigningFormat;
+use super::store::MAX_ORDER_BYTES;
+use crate::common::config::{parse_or, Config, ConfigError};
+
+/// Where the enclave signing key comes from at boot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeySource {
+    /// Fresh key from the RNG; lost on restart.
+    Random,
+    /// Seed sealed under AWS KMS, unsealed via attested decrypt.
+    Kms,
+}
+
+impl KeySource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            KeySource::Random => "random",
+            KeySource::Kms => "kms",
+        }
+    }
+}
+
+impl FromStr for KeySource {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "random" => Ok(KeySource::Random),
+            "kms" => Ok(KeySource::Kms),
+            other => Err(format!("unknown key source {:?}", other)),
+        }
+    }
+}
+
+/// A configured secret. `Debug` is redacted so the value never reaches logs.
+#[derive(Clone)]
+pub struct Secret(pub String);
+
+impl std::fmt::Debug for Secret {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Secret(***)")
+    }
+}
+
+/// A configured signing key seed. `Debug` is redacted like `Secret`.
+#[derive(Clone)]
+pub struct SecretSeed(pub [u8; 32]);
+
+impl std::fmt::Debug for SecretSeed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("SecretSeed(***)")
+    }
+}
+
+/// Order-processing knobs, read once from the environment at boot and kept
+/// in `AppState` so handlers never touch `std::env` on the request path.
+#[derive(Debug, Clone)]
+pub struct OrdersConfig {
+    /// Signing key source, from `NAUTILUS_KEY_SOURCE`.
+    pub key_source: KeySource,
+    /// Reject requests whose `metadata` object uses a top-level key that
+    /// collides with a server-authoritative response field.
+    pub reject_reserved_metadata_keys: bool,
+    /// Maximum number of array elements plus object entries, counted
+    /// recursively, allowed in `metadata`.
+    pub max_metadata_elements: usize,
+    /// Largest `metadata` accepted, as compact serialized JSON bytes, from
+    /// `MAX_METADATA_BYTES`.
+    pub max_metadata_bytes: usize,
+    /// Deepest nesting of arrays and objects allowed in `metadata`, from
+    /// `MAX_METADATA_DEPTH`. A bare scalar has depth 0.
+    pub max_metadata_depth: usize,
+    /// Length of the rolling per-customer spending window.
+    pub customer_window_ms: u64,
+    /// Per-currency cap on what one customer may initiate within the
+    /// window. Currencies not listed are uncapped; empty disables the check.
+    pub customer_caps: HashMap<String, u64>,
+    /// Upper bound on the V1 signing message, checked before signing.
+    pub max_signing_message_bytes: usize,
+    /// Allocate an `order_id` for `Initiate` requests that omit one.
+    pub allocate_order_ids: bool,
+    /// Maximum byte length of the request's `client_reference`.
+    pub max_client_reference_bytes: usize,
+    /// Encoding the V1 signature is computed over, from
+    /// `NAUTILUS_SIGNING_FORMAT` or else `SIGNING_FORMAT`.
+    pub signing_format: SigningFormat,
+    /// Maximum number of actions a single `order_id` may accumulate.
+    pub max_actions_per_order: u32,
+    /// Refuse to start if a test attestation fails at boot. When false the
+    /// failure is only logged, which suits dev builds without an NSM.
+    pub require_attestation_at_boot: bool,
+    /// Key style of JSON responses, for legacy camelCase clients.
+    pub field_naming: FieldNaming,
+    /// Token required in `x-admin-token` on `/admin/*` routes. Unset
+    /// disables the admin routes entirely.
+    pub admin_token: Option<Secret>,
+    /// Stop signing if `/admin/checkin` has not been called for this long.
+    /// Zero disables the dead-man's switch.
+    pub dead_man_window_ms: u64,
+    /// Approximate memory budget for all in-memory order state, from
+    /// `NAUTILUS_MAX_STATE_BYTES`. Requests that would add state get a 503
+    /// once it is reached. Must exceed `bounded_state_bytes`, so full
+    /// capacity-bounded stores alone can never exhaust it.
+    pub max_state_bytes: usize,
+    /// Throwaway signatures made at boot before serving traffic.
+    pub warmup_signs: u32,
+    /// When set, the only request `version` accepted.
+    pub strict_protocol_version: Option<u8>,
+    /// Operator co-signing endpoint for dual control. Needs
+    /// `operator_public_key` as well.
+    pub operator_cosign_url: Option<String>,
+    /// Base64 ed25519 key the operator co-signature must verify under.
+    pub operator_public_key: Option<String>,
+    /// Accept orders whose customer and merchant are the same party.
+    pub allow_self_dealing: bool,
+    /// Settled `(order_id, action)` pairs remembered for replay protection.
+    pub replay_guard_capacity: usize,
+    /// KMS key the sealed seed is decrypted under via attested kmstool
+    /// decrypt, from `SIGNING_KEY_KMS_ARN`. Takes precedence over
+    /// `key_source`.
+    pub kms_key_arn: Option<String>,
+    /// Signing key algorithm, from `SIGNING_SCHEME`.
+    pub signing_scheme: SchemeKind,
+    /// Fixed seed a random-source key is derived from instead of the RNG,
+    /// from `SIGNING_KEY_SEED_B64`: base64 of exactly 32 bytes, never
+    /// truncated or padded.
+    pub signing_key_seed: Option<SecretSeed>,
+    /// Ciphertext of the seed sealed under KMS, from the base64
+    /// `NAUTILUS_KMS_SEALED_SEED_B64`. Required when the key comes from KMS.
+    pub kms_sealed_seed: Option<Vec<u8>>,
+    /// `kmstool_enclave_cli` binary, from `NAUTILUS_KMSTOOL_PATH`.
+    pub kmstool_path: String,
+    /// vsock-proxy port forwarding to KMS, from `NAUTILUS_KMS_PROXY_PORT`.
+    pub kms_proxy_port: u16,
+    /// Most orders accepted in one `/orders/process_batch` call.
+    pub max_batch_size: usize,
+    /// Orders whose `client_timestamp_ms` is further than this from server
+    /// time, either way, are signed as `Rejected`. From `MAX_CLOCK_SKEW_MS`.
+    pub max_clock_skew_ms: u64,
+    /// Shared secret required in `x-api-key` on the order-signing routes,
+    /// from `ORDERS_API_KEY`. Unset leaves them open.
+    pub orders_api_key: Option<Secret>,
+    /// Largest request body accepted on the order routes, from
+    /// `MAX_BODY_BYTES`. Bigger bodies get 413 before any JSON is parsed.
+    pub max_body_bytes: NonZeroUsize,
+    /// Longest a request on the order routes may take, from
+    /// `REQUEST_TIMEOUT_MS`. Slower requests are answered with 504.
+    pub request_timeout_ms: u64,
+    /// Requests handled at once across the order routes, from
+    /// `MAX_CONCURRENT_REQUESTS`. Requests beyond it get 503 at once.
+    pub max_concurrent_requests: NonZeroUsize,
+    /// Sign the Blake2b-256 digest of the V1 signing message instead of the
+    /// message, from `NAUTILUS_HASH_BEFORE_SIGN`. Off by default.
+    pub hash_before_sign: bool,
+    /// Orders tracked at most, from `ORDER_STORE_CAPACITY`; past it the
+    /// least recently touched order is forgotten.
+    pub order_store_capacity: NonZeroUsize,
+    /// Escrow fee charged on each order's `amount`, from `FEE_BPS`.
+    pub fee_bps: BasisPoints,
+    /// Endpoint every signed response is POSTed to, from `WEBHOOK_URL`.
+    /// Needs `webhook_secret` as well.
+    pub webhook_url: Option<String>,
+    /// HMAC key for the webhook signature header, from `WEBHOOK_SECRET`.
+    pub webhook_secret: Option<Secret>,
+    /// File every signed response is appended to as a JSON line, from
+    /// `AUDIT_LOG_PATH`. Unset disables the audit log.
+    pub audit_log_path: Option<String>,
+    /// Sign each merchant's orders with its own key derived from the
+    /// enclave seed, from `NAUTILUS_PER_MERCHANT_KEYS`.
+    pub per_merchant_keys: bool,
+    /// Merchant ids that get a key when `per_merchant_keys` is set, from the
+    /// comma-separated `NAUTILUS_MERCHANTS`. Orders for any other merchant
+    /// are refused.
+    pub merchant_allowlist: Vec<String>,
+    /// How long a response stays cached under its `Idempotency-Key`.
+    pub idempotency_ttl_ms: u64,
+}
+
+impl Default for OrdersConfig {
+    fn default() -> Self {
+        Self {
+            key_source: KeySource::Random,
+            reject_reserved_metadata_keys: true,
+            max_metadata_elements: 256,
+            max_metadata_bytes: 8192,
+            max_metadata_depth: 8,
+            customer_window_ms: 24 * 60 * 60 * 1000,
+            customer_caps: HashMap::new(),
+            max_signing_message_bytes: 4096,
+            allocate_order_ids: false,
+            max_client_reference_bytes: 128,
+            signing_format: SigningFormat::Bcs,
+            max_actions_per_order: 16,
+            require_attestation_at_boot: false,
+            field_naming: FieldNaming::SnakeCase,
+            admin_token: None,
+            dead_man_window_ms: 0,
+            max_state_bytes: 256 * 1024 * 1024,
+            warmup_signs: 0,
+            strict_protocol_version: None,
+            operator_cosign_url: None,
+            operator_public_key: None,
+            allow_self_dealing: false,
+            replay_guard_capacity: 100_000,
+            kms_key_arn: None,
+            signing_scheme: SchemeKind::Ed25519,
+            signing_key_seed: None,
+            kms_sealed_seed: None,
+            kmstool_path: "/kmstool_enclave_cli".to_string(),
+            kms_proxy_port: 8000,
+            max_batch_size: 500,
+            max_clock_skew_ms: 5 * 60 * 1000,
+            orders_api_key: None,
+            max_body_bytes: NonZeroUsize::new(256 * 1024).expect("non-zero"),
+            request_timeout_ms: 10_000,
+            max_concurrent_requests: NonZeroUsize::new(256).expect("non-zero"),
+            hash_before_sign: false,
+            order_store_capacity: NonZeroUsize::new(100_000).expect("non-zero"),
+            fee_bps: BasisPoints::default(),
+            webhook_url: None,
+            webhook_secret: None,
+            audit_log_path: None,
+            per_merchant_keys: false,
+            merchant_allowlist: Vec::new(),
+            idempotency_ttl_ms: 60 * 60 * 1000,
+        }
+    }
+}
+
+impl OrdersConfig {
+    pub fn from_env() -> Result<Self, ConfigError> {
+        Self::from_lookup(|name| std::env::var(name).ok())
+    }
+
+    /// Load from `lookup`, collecting every bad setting into one
+    /// `ConfigError` like `Config::from_lookup`.
+    pub fn from_lookup(lookup: impl Fn(&str) -> Option<String>) -> Result<Self, ConfigError> {
+        let defaults = Self::default();
+        let mut errors = Vec::new();
+        let config = Self {
+            key_source: parse_or(
+                &lookup,
+                "NAUTILUS_KEY_SOURCE",
+                defaults.key_source,
+                &mut errors,
+            ),
+            reject_reserved_metadata_keys: parse_or(
+                &lookup,
+                "NAUTILUS_REJECT_RESERVED_METADATA_KEYS",
+                defaults.reject_reserved_metadata_keys,
+                &mut errors,
+            ),
+            max_metadata_elements: parse_or(
+                &lookup,
+                "NAUTILUS_MAX_METADATA_ELEMENTS",
+                defaults.max_metadata_elements,
+                &mut errors,
+            ),
+            max_metadata_bytes: parse_or(
+                &lookup,
+                "MAX_METADATA_BYTES",
+                defaults.max_metadata_bytes,
+                &mut errors,
+            ),
+            max_metadata_depth: parse_or(
+                &lookup,
+                "MAX_METADATA_DEPTH",
+                defaults.max_metadata_depth,
+                &mut errors,
+            ),
+            customer_window_ms: parse_or(
+                &lookup,
+                "NAUTILUS_CUSTOMER_WINDOW_MS",
+                defaults.customer_window_ms,
+                &mut errors,
+            ),
+            customer_caps: match lookup("NAUTILUS_CUSTOMER_CAPS") {
+                Some(raw) => parse_currency_caps(&raw).unwrap_or_else(|e| {
+                    errors.push(e);
+                    defaults.customer_caps
+                }),
+                None => defaults.customer_caps,
+            },
+            max_signing_message_bytes: parse_or(
+                &lookup,
+                "NAUTILUS_MAX_SIGNING_MESSAGE_BYTES",
+                defaults.max_signing_message_bytes,
+                &mut errors,
+            ),
+            allocate_order_ids: parse_or(
+                &lookup,
+                "NAUTILUS_ALLOCATE_ORDER_IDS",
+                defaults.allocate_order_ids,
+                &mut errors,
+            ),
+            max_client_reference_bytes: parse_or(
+                &lookup,
+                "NAUTILUS_MAX_CLIENT_REFERENCE_BYTES",
+                defaults.max_client_reference_bytes,
+                &mut errors,
+            ),
+            signing_format: parse_or(
+                &lookup,
+                "NAUTILUS_SIGNING_FORMAT",
+                parse_or(
+                    &lookup,
+                    "SIGNING_FORMAT",
+                    defaults.signing_format,
+                    &mut errors,
+                ),
+                &mut errors,
+            ),
+            max_actions_per_order: parse_or(
+                &lookup,
+                "NAUTILUS_MAX_ACTIONS_PER_ORDER",
+                defaults.max_actions_per_order,
+                &mut errors,
+            ),
+            require_attestation_at_boot: parse_or(
+                &lookup,
+                "NAUTILUS_REQUIRE_ATTESTATION_AT_BOOT",
+                defaults.require_attestation_at_boot,
+                &mut errors,
+            ),
+            field_naming: parse_or(
+                &lookup,
+                "NAUTILUS_JSON_FIELD_NAMING",
+                defaults.field_naming,
+                &mut errors,
+            ),
+            admin_token: lookup("NAUTILUS_ADMIN_TOKEN")
+                .filter(|t| !t.is_empty())
+                .map(Secret),
+            dead_man_window_ms: parse_or(
+                &lookup,
+                "NAUTILUS_DEAD_MAN_WINDOW_MS",
+                defaults.dead_man_window_ms,
+                &mut errors,
+            ),
+            max_state_bytes: parse_or(
+                &lookup,
+                "NAUTILUS_MAX_STATE_BYTES",
+                defaults.max_state_bytes,
+                &mut errors,
+            ),
+            warmup_signs: parse_or(
+                &lookup,
+                "NAUTILUS_WARMUP_SIGNS",
+                defaults.warmup_signs,
+                &mut errors,
+            ),
+            strict_protocol_version: match lookup("NAUTILUS_STRICT_PROTOCOL_VERSION") {
+                Some(_) => Some(parse_or(
+                    &lookup,
+                    "NAUTILUS_STRICT_PROTOCOL_VERSION",
+                    0u8,
+                    &mut errors,
+                )),
+                None => defaults.strict_protocol_version,
+            },
+            operator_cosign_url: lookup("NAUTILUS_OPERATOR_COSIGN_URL"),
+            operator_public_key: lookup("NAUTILUS_OPERATOR_PUBLIC_KEY"),
+            allow_self_dealing: parse_or(
+                &lookup,
+                "NAUTILUS_ALLOW_SELF_DEALING",
+                defaults.allow_self_dealing,
+                &mut errors,
+            ),
+            replay_guard_capacity: parse_or(
+                &lookup,
+                "NAUTILUS_REPLAY_GUARD_CAPACITY",
+                defaults.replay_guard_capacity,
+                &mut errors,
+            ),
+            kms_key_arn: lookup("SIGNING_KEY_KMS_ARN").filter(|arn| !arn.is_empty()),
+            signing_scheme: parse_or(
+                &lookup,
+                "SIGNING_SCHEME",
+                defaults.signing_scheme,
+                &mut errors,
+            ),
+            signing_key_seed: parse_base64(&lookup, "SIGNING_KEY_SEED_B64", &mut errors).and_then(
+                |seed| match <[u8; 32]>::try_from(seed) {
+                    Ok(seed) => Some(SecretSeed(seed)),
+                    Err(seed) => {
+                        errors.push(format!(
+                            "SIGNING_KEY_SEED_B64 is {} bytes, expected 32",
+                            seed.len()
+                        ));
+                        None
+                    }
+                },
+            ),
+            kms_sealed_seed: parse_base64(&lookup, "NAUTILUS_KMS_SEALED_SEED_B64", &mut errors),
+            kmstool_path: lookup("NAUTILUS_KMSTOOL_PATH")
+                .filter(|p| !p.is_empty())
+                .unwrap_or(defaults.kmstool_path),
+            kms_proxy_port: parse_or(
+                &lookup,
+                "NAUTILUS_KMS_PROXY_PORT",
+                defaults.kms_proxy_port,
+                &mut errors,
+            ),
+            max_batch_size: parse_or(
+                &lookup,
+                "NAUTILUS_MAX_BATCH_SIZE",
+                defaults.max_batch_size,
+                &mut errors,
+            ),
+            max_clock_skew_ms: parse_or(
+                &lookup,
+                "MAX_CLOCK_SKEW_MS",
+                defaults.max_clock_skew_ms,
+                &mut errors,
+            ),
+            orders_api_key: lookup("ORDERS_API_KEY")
+                .filter(|k| !k.is_empty())
+                .map(Secret),
+            max_body_bytes: parse_or(
+                &lookup,
+                "MAX_BODY_BYTES",
+                defaults.max_body_bytes,
+                &mut errors,
+            ),
+            request_timeout_ms: parse_or(
+                &lookup,
+                "REQUEST_TIMEOUT_MS",
+                defaults.request_timeout_ms,
+                &mut errors,
+            ),
+            max_concurrent_requests: parse_or(
+                &lookup,
+                "MAX_CONCURRENT_REQUESTS",
+                defaults.max_concurrent_requests,
+                &mut errors,
+            ),
+            hash_before_sign: parse_or(
+                &lookup,
+                "NAUTILUS_HASH_BEFORE_SIGN",
+                defaults.hash_before_sign,
+                &mut errors,
+            ),
+            order_store_capacity: parse_or(
+                &lookup,
+                "ORDER_STORE_CAPACITY",
+                defaults.order_store_capacity,
+                &mut errors,
+            ),
+            fee_bps: parse_or(&lookup, "FEE_BPS", defaults.fee_bps, &mut errors),
+            webhook_url: lookup("WEBHOOK_URL").filter(|u| !u.is_empty()),
+            webhook_secret: lookup("WEBHOOK_SECRET")
+                .filter(|s| !s.is_empty())
+                .map(Secret),
+            audit_log_path: lookup("AUDIT_LOG_PATH").filter(|p| !p.is_empty()),
+            per_merchant_keys: parse_or(
+                &lookup,
+                "NAUTILUS_PER_MERCHANT_KEYS",
+                defaults.per_merchant_keys,
+                &mut errors,
+            ),
+            merchant_allowlist: match lookup("NAUTILUS_MERCHANTS") {
+                Some(raw) => raw
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|merchant| !merchant.is_empty())
+                    .map(str::to_string)
+                    .collect(),
+                None => defaults.merchant_allowlist,
+            },
+            idempotency_ttl_ms: parse_or(
+                &lookup,
+                "NAUTILUS_IDEMPOTENCY_TTL_MS",
+                defaults.idempotency_ttl_ms,
+                &mut errors,
+            ),
+        };
+        if config.bounded_state_bytes() >= config.max_state_bytes {
+            errors.push(format!(
+                "NAUTILUS_MAX_STATE_BYTES {} must exceed the {} bytes a full order store \
+                 (ORDER_STORE_CAPACITY) and replay guard (NAUTILUS_REPLAY_GUARD_CAPACITY) hold",
+                config.max_state_bytes,
+                config.bounded_state_bytes()
+            ));
+        }
+        if config.key_from_kms() && config.kms_sealed_seed.is_none() {
+            errors.push(
+                "NAUTILUS_KMS_SEALED_SEED_B64 must be set to unseal the signing key via KMS"
+                    .to_string(),
+            );
+        }
+        if config.per_merchant_keys && config.merchant_allowlist.is_empty() {
+            errors.push(
+                "NAUTILUS_PER_MERCHANT_KEYS needs the merchant ids listed in NAUTILUS_MERCHANTS"
+                    .to_string(),
+            );
+        }
+        if errors.is_empty() {
+            Ok(config)
+        } else {
+            Err(ConfigError(errors))
+        }
+    }
+
+    /// Whether the signing key is unsealed via KMS, by `kms_key_arn` or
+    /// `KeySource::Kms`.
+    pub fn key_from_kms(&self) -> bool {
+        self.kms_key_arn.is_some() || self.key_source == KeySource::Kms
+    }
+
+    /// Approximate memory held by the order store and replay guard when both
+    /// are full. Neither ever shrinks back, so this much of
+    /// `max_state_bytes` is taken for good under steady traffic.
+    pub fn bounded_state_bytes(&self) -> usize {
+        self.order_store_capacity
+            .get()
+            .saturating_mul(MAX_ORDER_BYTES)
+            .saturating_add(
+                self.replay_guard_capacity
+                    .saturating_mul(REPLAY_ENTRY_BYTES),
+            )
+    }
+}
+
+/// Emit a single structured event summarising the security-relevant
+/// settings actually in effect, so a misconfiguration is obvious at boot.
+pub fn log_security_posture(server: &Config, config: &OrdersConfig) {
+    info!(
+        key_source = if config.kms_key_arn.is_some() {
+            "kms_arn"
+        } else if config.key_source == KeySource::Random && config.signing_key_seed.is_some() {
+            "seed_env"
+        } else {
+            config.key_source.as_str()
+        },
+        scheme = config.signing_scheme.as_str(),
+        signing_format = config.signing_format.as_str(),
+        cors = if server.cors_origins.is_some() {
+            "allowlist"
+        } else {
+            "any"
+        },
+        reject_reserved_metadata_keys = config.reject_reserved_metadata_keys,
+        max_metadata_elements = config.max_metadata_elements,
+        max_metadata_bytes = config.max_metadata_bytes,
+        max_metadata_depth = config.max_metadata_depth,
+        customer_caps = config.customer_caps.len(),
+        max_signing_message_bytes = config.max_signing_message_bytes,
+        max_actions_per_order = config.max_actions_per_order,
+        require_attestation_at_boot = config.require_attestation_at_boot,
+        admin_token_set = config.admin_token.is_some(),
+        dead_man_window_ms = config.dead_man_window_ms,
+        max_state_bytes = config.max_state_bytes,
+        strict_protocol_version = ?config.strict_protocol_version,
+        operator_cosign = config.operator_cosign_url.is_some(),
+        allow_self_dealing = config.allow_self_dealing,
+        replay_guard_capacity = config.replay_guard_capacity,
+        max_clock_skew_ms = config.max_clock_skew_ms,
+        orders_api_key_set = config.orders_api_key.is_some(),
+        max_body_bytes = config.max_body_bytes.get(),
+        request_timeout_ms = config.request_timeout_ms,
+        max_concurrent_requests = config.max_concurrent_requests.get(),
+        hash_before_sign = config.hash_before_sign,
+        order_store_capacity = config.order_store_capacity.get(),
+        fee_bps = config.fee_bps.get(),
+        webhook = config.webhook_url.is_some(),
+        audit_log = config.audit_log_path.is_some(),
+        per_merchant_keys = config.per_merchant_keys,
+        merchants = config.merchant_allowlist.len(),
+        idempotency_ttl_ms = config.idempotency_ttl_ms,
+        "Security posture"
+    );
+}
+
+/// The bytes of the base64 variable `name`, or `None` when unset or not
+/// base64, which is reported in `errors`.
+fn parse_base64(
+    lookup: &impl Fn(&str) -> Option<String>,
+    name: &str,
+    errors: &mut Vec<String>,
+) -> Option<Vec<u8>> {
+    let raw = lookup(name)?;
+    B64.decode(raw.trim())
+        .map_err(|e| errors.push(format!("{} is not base64: {}", name, e)))
+        .ok()
+}
+
+/// Parse `CUR=amount` pairs separated by commas, e.g. `USD=100000,EUR=90000`.
+fn parse_currency_caps(raw: &str) -> Result<HashMap<String, u64>, String> {
+    raw.split(',')
+        .map(str::trim)
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (currency, cap) = pair.split_once('=').ok_or_else(|| {
+                format!("NAUTILUS_CUSTOMER_CAPS entry {:?} is not CUR=amount", pair)
+            })?;
+            let cap = cap.trim().parse::<u64>().map_err(|_| {
+                format!(
+                    "NAUTILUS_CUSTOMER_CAPS entry {:?} has an invalid amount",
+                    pair
+                )
+            })?;
+            Ok((currency.trim().to_string(), cap))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lookup(vars: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        move |name| vars.get(name).cloned()
+    }
+
+    #[test]
+    fn scheme_and_seed_come_from_the_lookup() {
+        let config = OrdersConfig::from_lookup(lookup(&[
+            ("SIGNING_SCHEME", "secp256k1"),
+            (
+                "SIGNING_KEY_SEED_B64",
+                "AAECAwQFBgcICQoLDA0ODxAREhMUFRYXGBkaGxwdHh8=",
+            ),
+        ]))
+        .unwrap();
+        assert_eq!(config.signing_scheme, SchemeKind::Secp256k1);
+        assert_eq!(
+            config.signing_key_seed.unwrap().0,
+            core::array::from_fn::<u8, 32, _>(|i| i as u8)
+        );
+    }
+
+    #[test]
+    fn seeds_must_be_base64_of_32_bytes() {
+        for (raw, expected) in [
+            ("AAEC", "SIGNING_KEY_SEED_B64 is 3 bytes, expected 32"),
+            ("%%%", "SIGNING_KEY_SEED_B64 is not base64"),
+        ] {
+            let err =
+                OrdersConfig::from_lookup(lookup(&[("SIGNING_KEY_SEED_B64", raw)])).unwrap_err();
+            assert!(err.0[0].starts_with(expected), "{}", err);
+        }
+    }
+
+    #[test]
+    fn kms_settings_are_validated_together() {
+        let err = OrdersConfig::from_lookup(lookup(&[
+            ("NAUTILUS_KEY_SOURCE", "kms"),
+            ("NAUTILUS_KMS_PROXY_PORT", "70000"),
+        ]))
+        .unwrap_err();
+        assert_eq!(err.0.len(), 2, "{}", err);
+        assert!(err.0[0].starts_with("NAUTILUS_KMS_PROXY_PORT"));
+        assert!(err.0[1].starts_with("NAUTILUS_KMS_SEALED_SEED_B64"));
+
+        let config = OrdersConfig::from_lookup(lookup(&[
+            ("NAUTILUS_KEY_SOURCE", "kms"),
+            ("NAUTILUS_KMS_SEALED_SEED_B64", "c2VhbGVk"),
+            ("NAUTILUS_KMSTOOL_PATH", "/opt/kmstool"),
+            ("NAUTILUS_KMS_PROXY_PORT", "8001"),
+        ]))
+        .unwrap();
+        assert_eq!(config.kms_sealed_seed.as_deref(), Some(&b"sealed"[..]));
+        assert_eq!(config.kmstool_path, "/opt/kmstool");
+        assert_eq!(config.kms_proxy_port, 8001);
+    }
+
+    #[test]
+    fn every_bad_setting_is_reported_in_one_pass() {
+        let err = OrdersConfig::from_lookup(lookup(&[
+            ("SIGNING_SCHEME", "rsa"),
+            ("REQUEST_TIMEOUT_MS", "soon"),
+            ("NAUTILUS_CUSTOMER_CAPS", "USD"),
+        ]))
+        .unwrap_err();
+        assert_eq!(err.0.len(), 3, "{}", err);
+    }
+
+    /// Collects everything a `fmt` subscriber writes.
+    #[derive(Clone, Default)]
+    struct Capture(std::sync::Arc<std::sync::Mutex<Vec<u8>>>);
+
+    impl std::io::Write for Capture {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn security_posture_reflects_the_config() {
+        let server =
+            Config::from_lookup(lookup(&[("CORS_ALLOWED_ORIGINS", "https://shop.example")]))
+                .unwrap();
+        let config = OrdersConfig::from_lookup(lookup(&[
+            ("SIGNING_SCHEME", "secp256k1"),
+            ("SIGNING_KEY_KMS_ARN", "arn:aws:kms:us-east-1:1:key/k"),
+            ("NAUTILUS_KMS_SEALED_SEED_B64", "c2VhbGVk"),
+            ("MAX_BODY_BYTES", "4096"),
+        ]))
+        .unwrap();
+        let capture = Capture::default();
+        let writer = capture.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .json()
+            .with_writer(move || writer.clone())
+            .finish();
+        tracing::subscriber::with_default(subscriber, || log_security_posture(&server, &config));
+        let logged = String::from_utf8(capture.0.lock().unwrap().clone()).unwrap();
+        assert!(logged.contains(r#""key_source":"kms_arn""#), "{}", logged);
+        assert!(logged.contains(r#""scheme":"secp256k1""#), "{}", logged);
+        assert!(logged.contains(r#""cors":"allowlist""#), "{}", logged);
+        assert!(logged.contains(r#""max_body_bytes":4096"#), "{}", logged);
+    }
+
+    #[test]
+    fn currency_caps_parse() {
+        let caps = parse_currency_caps("USD=100, EUR=90").unwrap();
+        assert_eq!(caps.get("USD"), Some(&100));
+        assert_eq!(caps.get("EUR"), Some(&90));
+        assert!(parse_currency_caps("USD=lots").is_err());
+    }
+
+    #[test]
+    fn state_budget_must_exceed_what_full_stores_hold() {
+        let defaults = OrdersConfig::from_lookup(lookup(&[])).unwrap();
+        assert!(defaults.bounded_state_bytes() < defaults.max_state_bytes);
+
+        let err = OrdersConfig::from_lookup(lookup(&[
+            ("ORDER_STORE_CAPACITY", "1000"),
+            ("NAUTILUS_REPLAY_GUARD_CAPACITY", "0"),
+            (
+                "NAUTILUS_MAX_STATE_BYTES",
+                &(1000 * MAX_ORDER_BYTES).to_string(),
+            ),
+        ]))
+        .unwrap_err();
+        assert_eq!(err.0.len(), 1, "{}", err);
+        assert!(err.0[0].starts_with("NAUTILUS_MAX_STATE_BYTES"));
+    }
+
+    #[test]
+    fn per_merchant_keys_need_an_allowlist() {
+        let err = OrdersConfig::from_lookup(lookup(&[("NAUTILUS_PER_MERCHANT_KEYS", "true")]))
+            .unwrap_err();
+        assert!(err.0[0].contains("NAUTILUS_MERCHANTS"), "{}", err);
+
+        let config = OrdersConfig::from_lookup(lookup(&[
+            ("NAUTILUS_PER_MERCHANT_KEYS", "true"),
+            ("NAUTILUS_MERCHANTS", " acme, globex ,,"),
+        ]))
+        .unwrap();
+        assert_eq!(config.merchant_allowlist, vec!["acme", "globex"]);
+    }
+
+    #[test]
+    fn zero_body_and_concurrency_limits_are_refused() {
+        let err = OrdersConfig::from_lookup(lookup(&[
+            ("MAX_BODY_BYTES", "0"),
+            ("MAX_CONCURRENT_REQUESTS", "0"),
+        ]))
+        .unwrap_err();
+        assert_eq!(err.0.len(), 2, "{}", err);
+        assert!(err.0[0].starts_with("MAX_BODY_BYTES"));
+        assert!(err.0[1].starts_with("MAX_CONCURRENT_REQUESTS"));
+    }
+}
@@ -0,0 +1,138 @@
+// Copyright (c), Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use ciborium::value::{Integer, Value};
+
+use super::crypto;
+
+/// COSE header label for the algorithm (RFC 8152 §3.1).
+const HEADER_ALG: i64 = 1;
+/// COSE header label for the key identifier.
+const HEADER_KID: i64 = 4;
+/// COSE algorithm identifier for EdDSA.
+const ALG_EDDSA: i64 = -8;
+/// COSE algorithm identifier for ECDSA secp256k1 with SHA-256 (RFC 8812).
+const ALG_ES256K: i64 = -47;
+/// CBOR tag marking a COSE_Sign1 message.
+const COSE_SIGN1_TAG: u64 = 18;
+
+fn to_cbor(value: &Value) -> Vec<u8> {
+    let mut out = Vec::new();
+    ciborium::into_writer(value, &mut out).expect("CBOR encoding into a Vec should not fail");
+    out
+}
+
+/// Wrap `payload` in a tagged COSE_Sign1 message signed by the `scope` key.
+/// The signature covers the RFC 8152 `Sig_structure` with an empty external
+/// AAD, so stock COSE libraries can verify it against the JWK.
+pub fn sign1(payload: &[u8], scope: crypto::KeyScope) -> Result<Vec<u8>, &'static str> {
+    let protected = to_cbor(&Value::Map(vec![(
+        Value::Integer(Integer::from(HEADER_ALG)),
+        Value::Integer(Integer::from(match crypto::active_scheme()? {
+            crypto::SchemeKind::Ed25519 => ALG_EDDSA,
+            crypto::SchemeKind::Secp256k1 => ALG_ES256K,
+        })),
+    )]));
+    let sig_structure = Value::Array(vec![
+        Value::Text("Signature1".to_string()),
+        Value::Bytes(protected.clone()),
+        Value::Bytes(Vec::new()),
+        Value::Bytes(payload.to_vec()),
+    ]);
+    let signature = crypto::sign_in(scope, &to_cbor(&sig_structure))?;
+    let unprotected = Value::Map(vec![(
+        Value::Integer(Integer::from(HEADER_KID)),
+        Value::Bytes(crypto::public_key_fingerprint_in(scope)?.into_bytes()),
+    )]);
+    Ok(to_cbor(&Value::Tag(
+        COSE_SIGN1_TAG,
+        Box::new(Value::Array(vec![
+            Value::Bytes(protected),
+            unprotected,
+            Value::Bytes(payload.to_vec()),
+            Value::Bytes(signature.to_vec()),
+        ])),
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixed_seed(seed: &mut [u8; 32]) -> Result<(), &'static str> {
+        seed.fill(7);
+        Ok(())
+    }
+
+    /// Verify a tagged COSE_Sign1 the way an RFC 8152 verifier does: rebuild
+    /// the `Sig_structure` from the message itself and check it against
+    /// `public_key`.
+    fn verify_sign1(message: &[u8], public_key: &str) -> Result<Vec<u8>, &'static str> {
+        let Value::Tag(COSE_SIGN1_TAG, body) =
+            ciborium::from_reader(message).map_err(|_| "not_cbor")?
+        else {
+            return Err("not_cose_sign1");
+        };
+        let Value::Array(parts) = *body else {
+            return Err("not_an_array");
+        };
+        let [protected, _unprotected, payload, signature] = &parts[..] else {
+            return Err("malformed_sign1");
+        };
+        let (Some(protected), Some(payload), Some(signature)) = (
+            protected.as_bytes(),
+            payload.as_bytes(),
+            signature.as_bytes(),
+        ) else {
+            return Err("malformed_sign1");
+        };
+        let sig_structure = Value::Array(vec![
+            Value::Text("Signature1".to_string()),
+            Value::Bytes(protected.clone()),
+            Value::Bytes(Vec::new()),
+            Value::Bytes(payload.clone()),
+        ]);
+        let signature: [u8; 64] = signature[..].try_into().map_err(|_| "bad_signature")?;
+        match crypto::verify(&to_cbor(&sig_structure), &signature, public_key)? {
+            true => Ok(payload.clone()),
+            false => Err("bad_signature"),
+        }
+    }
+
+    #[test]
+    fn sign1_verifies_over_the_sig_structure() {
+        crypto::ensure_initialized_with(crypto::SchemeKind::Ed25519, fixed_seed).unwrap();
+        let public_key = crypto::public_key_base64().unwrap();
+        let message = sign1(b"payload", crypto::KeyScope::Enclave).unwrap();
+        assert_eq!(verify_sign1(&message, &public_key).unwrap(), b"payload");
+
+        // The protected header names EdDSA.
+        let Value::Tag(_, body) = ciborium::from_reader(&message[..]).unwrap() else {
+            unreachable!()
+        };
+        let Value::Array(parts) = *body else {
+            unreachable!()
+        };
+        let protected: Value =
+            ciborium::from_reader(parts[0].as_bytes().unwrap().as_slice()).unwrap();
+        assert_eq!(
+            protected,
+            Value::Map(vec![(
+                Value::Integer(Integer::from(HEADER_ALG)),
+                Value::Integer(Integer::from(ALG_EDDSA)),
+            )])
+        );
+
+        // A payload swapped after signing no longer verifies.
+        let tampered = to_cbor(&Value::Tag(
+            COSE_SIGN1_TAG,
+            Box::new(Value::Array(vec![
+                parts[0].clone(),
+                parts[1].clone(),
+                Value::Bytes(b"other".to_vec()),
+                parts[3].clone(),
+            ])),
+        ));
+        assert_eq!(verify_sign1(&tampered, &public_key), Err("bad_signature"));
+    }
+}
@@ -0,0 +1,192 @@
+// Copyright (c), Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use base64::engine::general_purpose::STANDARD as B64;
+use base64::Engine;
+use ed25519_dalek::{Signature, Verifier, VerifyingKey};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tracing::info;
+
+use crate::EnclaveError;
+
+use super::OrdersConfig;
+
+#[derive(Serialize)]
+struct CosignRequest {
+    /// base64 of the exact bytes the enclave signature covers.
+    message: String,
+}
+
+#[derive(Deserialize)]
+struct CosignResponse {
+    /// base64 ed25519 signature by the operator key.
+    signature: String,
+}
+
+/// Operator-held co-signer for dual control. The enclave signs first; the
+/// same message is then posted to the operator's endpoint and the returned
+/// signature is checked against the configured operator key before it is
+/// attached to the response.
+pub struct OperatorCosigner {
+    url: String,
+    public_key: VerifyingKey,
+    client: Client,
+}
+
+impl OperatorCosigner {
+    /// `None` unless both the endpoint and the operator key are configured;
+    /// setting only one of them is an error.
+    pub fn from_config(config: &OrdersConfig) -> Result<Option<Self>, String> {
+        let (url, key_b64) = match (&config.operator_cosign_url, &config.operator_public_key) {
+            (None, None) => return Ok(None),
+            (Some(url), Some(key)) => (url, key),
+            _ => {
+                return Err(
+                    "NAUTILUS_OPERATOR_COSIGN_URL and NAUTILUS_OPERATOR_PUBLIC_KEY \
+                            must be set together"
+                        .to_string(),
+                )
+            }
+        };
+        let key_bytes: [u8; 32] = B64
+            .decode(key_b64.trim())
+            .map_err(|e| format!("NAUTILUS_OPERATOR_PUBLIC_KEY is not base64: {}", e))?
+            .try_into()
+            .map_err(|_| "NAUTILUS_OPERATOR_PUBLIC_KEY must be 32 bytes".to_string())?;
+        let public_key = VerifyingKey::from_bytes(&key_bytes)
+            .map_err(|e| format!("NAUTILUS_OPERATOR_PUBLIC_KEY is not an ed25519 key: {}", e))?;
+        let client = Client::builder()
+            .timeout(Duration::from_secs(5))
+            .build()
+            .map_err(|e| format!("co-signer client: {}", e))?;
+        Ok(Some(Self {
+            url: url.clone(),
+            public_key,
+            client,
+        }))
+    }
+
+    /// Obtain and verify the operator signature over `message`, returned as
+    /// base64. A missing or invalid co-signature fails the request rather
+    /// than shipping a single-signed response.
+    pub async fn cosign(&self, message: &[u8]) -> Result<String, EnclaveError> {
+        let unavailable = |message: String| EnclaveError::Unavailable {
+            code: "cosigner_unavailable",
+            message,
+        };
+        let resp: CosignResponse = self
+            .client
+            .post(&self.url)
+            .json(&CosignRequest {
+                message: B64.encode(message),
+            })
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .map_err(|e| unavailable(format!("co-signer request failed: {}", e)))?
+            .json()
+            .await
+            .map_err(|e| unavailable(format!("co-signer response is malformed: {}", e)))?;
+        let sig_bytes: [u8; 64] = B64
+            .decode(resp.signature.trim())
+            .ok()
+            .and_then(|bytes| bytes.try_into().ok())
+            .ok_or_else(|| unavailable("co-signature is not a base64 ed25519 signature".into()))?;
+        self.public_key
+            .verify(message, &Signature::from_bytes(&sig_bytes))
+            .map_err(|_| unavailable("co-signature does not verify".to_string()))?;
+        info!("Operator co-signature verified");
+        Ok(resp.signature)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::orders::crypto;
+    use ed25519_dalek::{Signer, SigningKey};
+
+    fn fixed_seed(seed: &mut [u8; 32]) -> Result<(), &'static str> {
+        seed.fill(7);
+        Ok(())
+    }
+
+    /// Serve a co-signer that signs every message with `key`, returning the
+    /// URL to post to.
+    async fn mock_cosigner(key: SigningKey) -> String {
+        let app = axum::Router::new().route(
+            "/cosign",
+            axum::routing::post(move |axum::Json(req): axum::Json<serde_json::Value>| {
+                let message = B64.decode(req["message"].as_str().unwrap()).unwrap();
+                let signature = B64.encode(key.sign(&message).to_bytes());
+                async move { axum::Json(serde_json::json!({ "signature": signature })) }
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        format!("http://{}/cosign", addr)
+    }
+
+    fn cosigner(url: String, operator: &SigningKey) -> OperatorCosigner {
+        OperatorCosigner::from_config(&OrdersConfig {
+            operator_cosign_url: Some(url),
+            operator_public_key: Some(B64.encode(operator.verifying_key().to_bytes())),
+            ..Default::default()
+        })
+        .unwrap()
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn enclave_and_operator_signatures_both_verify() {
+        crypto::ensure_initialized_with(crypto::SchemeKind::Ed25519, fixed_seed).unwrap();
+        let operator = SigningKey::from_bytes(&[3; 32]);
+        let cosigner = cosigner(mock_cosigner(operator.clone()).await, &operator);
+
+        let message = b"order signing message";
+        let enclave_signature = crypto::sign(message).unwrap();
+        let operator_signature = cosigner.cosign(message).await.unwrap();
+        let operator_signature: [u8; 64] =
+            B64.decode(operator_signature).unwrap().try_into().unwrap();
+
+        let enclave_key = crypto::public_key_base64().unwrap();
+        let operator_key = B64.encode(operator.verifying_key().to_bytes());
+        assert_eq!(
+            crypto::verify(message, &enclave_signature, &enclave_key),
+            Ok(true)
+        );
+        assert_eq!(
+            crypto::verify(message, &operator_signature, &operator_key),
+            Ok(true)
+        );
+    }
+
+    #[tokio::test]
+    async fn co_signature_under_another_key_is_refused() {
+        let operator = SigningKey::from_bytes(&[3; 32]);
+        let impostor = SigningKey::from_bytes(&[4; 32]);
+        let cosigner = cosigner(mock_cosigner(impostor).await, &operator);
+        assert!(matches!(
+            cosigner.cosign(b"order signing message").await,
+            Err(EnclaveError::Unavailable {
+                code: "cosigner_unavailable",
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn half_configured_cosigner_is_an_error() {
+        let config = OrdersConfig {
+            operator_cosign_url: Some("http://127.0.0.1:1/cosign".to_string()),
+            ..Default::default()
+        };
+        assert!(OperatorCosigner::from_config(&config).is_err());
+        assert!(OperatorCosigner::from_config(&OrdersConfig::default())
+            .unwrap()
+            .is_none());
+    }
+}
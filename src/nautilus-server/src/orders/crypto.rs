@@ -1,39 +1,875 @@
 // Copyright (c), Mysten Labs, Inc.
 // SPDX-License-Identifier: Apache-2.0
 
-use base64::engine::general_purpose::STANDARD as B64;
+use base64::engine::general_purpose::{STANDARD as B64, URL_SAFE_NO_PAD as B64URL};
 use base64::Engine;
-use ed25519_dalek::{Signer, SigningKey};
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use fastcrypto::encoding::{Encoding, Hex};
+use fastcrypto::hash::{HashFunction, Sha256};
+use fastcrypto::hmac::{hkdf_sha3_256, HkdfIkm};
+use fastcrypto::secp256k1::{
+    Secp256k1KeyPair, Secp256k1PrivateKey, Secp256k1PublicKey, Secp256k1Signature,
+};
+use fastcrypto::traits::{KeyPair as _, Signer as _, ToFromBytes, VerifyingKey as FcVerifyingKey};
 use once_cell::sync::OnceCell;
-use tracing::info;
-
-static SIGNING_KEY: OnceCell<SigningKey> = OnceCell::new();
-
-pub fn ensure_initialized() -> Result<(), &'static str> {
-    SIGNING_KEY.get_or_try_init(|| {
-        info!("🔧 Generating new signing key...");
-        let mut seed = [0u8; 32];
-        getrandom::getrandom(&mut seed).map_err(|_| "rng_unavailable")?;
-        let sk = SigningKey::from_bytes(&seed);
-        info!("✅ Signing key generated successfully");
-        Ok::<SigningKey, &'static str>(sk)
+use serde::Serialize;
+use serde_json::{json, Value};
+use spki::der::asn1::{BitString, ObjectIdentifier};
+use spki::der::pem::LineEnding;
+use spki::der::{Any, EncodePem};
+use spki::{AlgorithmIdentifierOwned, SubjectPublicKeyInfoOwned};
+use std::collections::{BTreeMap, VecDeque};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use tracing::{info, warn};
+
+use super::kms::KmsDecrypt;
+use crate::common::clock::unix_time_ms;
+
+/// Current enclave key; replaced by `rotate`.
+static SIGNING_KEY: RwLock<Option<Arc<EnclaveKey>>> = RwLock::new(None);
+static WARMED_UP: AtomicBool = AtomicBool::new(false);
+
+/// Public halves of keys replaced by `rotate`, oldest first.
+static RETIRED_KEYS: Mutex<VecDeque<RetiredKey>> = Mutex::new(VecDeque::new());
+/// Retired keys still accepted by `/orders/verify`; older ones are dropped.
+const MAX_RETIRED_KEYS: usize = 4;
+
+/// Seed the enclave key was derived from, kept to derive merchant keys.
+static MASTER_SEED: OnceCell<[u8; 32]> = OnceCell::new();
+/// Keys of the allowlisted merchants, by merchant id, derived once at boot
+/// by `derive_merchant_keys`. Merchant ids come from clients, so no other
+/// merchant ever gets a key.
+static MERCHANT_KEYS: RwLock<BTreeMap<String, Arc<EnclaveKey>>> = RwLock::new(BTreeMap::new());
+
+/// HKDF salt for merchant keys. Changing it changes every merchant key.
+const MERCHANT_KEY_SALT: &[u8] = b"nautilus/merchant-key/v1";
+
+/// Signature algorithm of the enclave signing key, from `SIGNING_SCHEME`.
+/// The signing message is the same under either; only the key and
+/// signature algorithm change.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SchemeKind {
+    #[default]
+    Ed25519,
+    /// ECDSA over secp256k1 with SHA-256, as Sui's `ecdsa_k1` verifies it.
+    Secp256k1,
+}
+
+impl SchemeKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SchemeKind::Ed25519 => "ed25519",
+            SchemeKind::Secp256k1 => "secp256k1",
+        }
+    }
+}
+
+impl FromStr for SchemeKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ed25519" => Ok(SchemeKind::Ed25519),
+            "secp256k1" => Ok(SchemeKind::Secp256k1),
+            other => Err(format!("unknown signing scheme {:?}", other)),
+        }
+    }
+}
+
+/// The enclave signing key under its active scheme.
+enum EnclaveKey {
+    Ed25519(SigningKey),
+    Secp256k1(Secp256k1KeyPair),
+}
+
+impl EnclaveKey {
+    /// Derive a key for `scheme` from a 32-byte seed.
+    fn from_seed_as(scheme: SchemeKind, seed: &[u8; 32]) -> Result<Self, &'static str> {
+        match scheme {
+            SchemeKind::Ed25519 => Ok(EnclaveKey::Ed25519(SigningKey::from_bytes(seed))),
+            SchemeKind::Secp256k1 => {
+                let sk = Secp256k1PrivateKey::from_bytes(seed).map_err(|_| "seed_invalid")?;
+                Ok(EnclaveKey::Secp256k1(Secp256k1KeyPair::from(sk)))
+            }
+        }
+    }
+
+    fn scheme(&self) -> SchemeKind {
+        match self {
+            EnclaveKey::Ed25519(_) => SchemeKind::Ed25519,
+            EnclaveKey::Secp256k1(_) => SchemeKind::Secp256k1,
+        }
+    }
+
+    /// Raw public key: 32 bytes for ed25519, 33-byte compressed point for
+    /// secp256k1.
+    fn public_key_bytes(&self) -> Vec<u8> {
+        match self {
+            EnclaveKey::Ed25519(sk) => sk.verifying_key().to_bytes().to_vec(),
+            EnclaveKey::Secp256k1(kp) => kp.public().as_bytes().to_vec(),
+        }
+    }
+
+    /// Hex of the first 8 bytes of SHA-256 over the raw public key.
+    fn kid(&self) -> String {
+        kid_of(&self.public_key_bytes())
+    }
+
+    /// See `public_key_jwk`.
+    fn jwk(&self) -> Value {
+        let kid = self.kid();
+        match self {
+            EnclaveKey::Ed25519(sk) => json!({
+                "kty": "OKP",
+                "crv": "Ed25519",
+                "x": B64URL.encode(sk.verifying_key().to_bytes()),
+                "kid": kid,
+                "use": "sig",
+                "alg": "EdDSA",
+            }),
+            EnclaveKey::Secp256k1(kp) => {
+                // SEC1 uncompressed point: 0x04 || x || y.
+                let point = kp.public().pubkey.serialize_uncompressed();
+                json!({
+                    "kty": "EC",
+                    "crv": "secp256k1",
+                    "x": B64URL.encode(&point[1..33]),
+                    "y": B64URL.encode(&point[33..65]),
+                    "kid": kid,
+                    "use": "sig",
+                    "alg": "ES256K",
+                })
+            }
+        }
+    }
+
+    fn sign(&self, message: &[u8]) -> [u8; 64] {
+        match self {
+            EnclaveKey::Ed25519(sk) => sk.sign(message).to_bytes(),
+            EnclaveKey::Secp256k1(kp) => {
+                let sig: Secp256k1Signature = kp.sign(message);
+                sig.as_ref()
+                    .try_into()
+                    .expect("secp256k1 compact signatures are 64 bytes")
+            }
+        }
+    }
+}
+
+/// Which key an operation uses: the enclave key, or the key derived from
+/// it for one merchant so a leaked merchant key exposes only that merchant.
+#[derive(Debug, Clone, Copy, Default)]
+pub enum KeyScope<'a> {
+    #[default]
+    Enclave,
+    Merchant(&'a str),
+}
+
+/// Error returned by key operations before a key has been installed.
+pub const KEY_UNINITIALIZED: &str = "signing_key_uninitialized";
+
+fn signing_key() -> Result<Arc<EnclaveKey>, &'static str> {
+    SIGNING_KEY
+        .read()
+        .expect("signing key lock poisoned")
+        .clone()
+        .ok_or(KEY_UNINITIALIZED)
+}
+
+/// Derive the `scheme` key for `merchant`: HKDF-SHA3-256 over `master`,
+/// with the merchant id as `info`, yields its seed. Stable for a given
+/// master seed, scheme and merchant id.
+fn derive_merchant_key(
+    master: &[u8; 32],
+    scheme: SchemeKind,
+    merchant: &str,
+) -> Result<EnclaveKey, &'static str> {
+    let ikm = HkdfIkm::from_bytes(master).map_err(|_| "seed_invalid")?;
+    let seed: [u8; 32] = hkdf_sha3_256(&ikm, MERCHANT_KEY_SALT, merchant.as_bytes(), 32)
+        .map_err(|_| "merchant_key_derivation_failed")?
+        .try_into()
+        .map_err(|_| "merchant_key_derivation_failed")?;
+    EnclaveKey::from_seed_as(scheme, &seed)
+}
+
+/// Derive the key of every merchant in `merchants` from the master seed,
+/// under the scheme of the installed key. Run once at boot, after the key
+/// is installed and before the attestation commits to `merchant_keys_digest`.
+pub fn derive_merchant_keys(merchants: &[String]) -> Result<(), &'static str> {
+    let scheme = signing_key()?.scheme();
+    let master = MASTER_SEED.get().ok_or(KEY_UNINITIALIZED)?;
+    let mut keys = MERCHANT_KEYS.write().expect("merchant keys lock poisoned");
+    for merchant in merchants {
+        let key = derive_merchant_key(master, scheme, merchant)?;
+        keys.insert(merchant.clone(), Arc::new(key));
+    }
+    info!(merchants = keys.len(), "Merchant keys derived");
+    Ok(())
+}
+
+/// The key derived for `merchant`, or `unknown_merchant` if it is not on
+/// the allowlist.
+fn merchant_key(merchant: &str) -> Result<Arc<EnclaveKey>, &'static str> {
+    MERCHANT_KEYS
+        .read()
+        .expect("merchant keys lock poisoned")
+        .get(merchant)
+        .cloned()
+        .ok_or("unknown_merchant")
+}
+
+/// Run `f` with the key for `scope`.
+fn with_key<T>(scope: KeyScope, f: impl FnOnce(&EnclaveKey) -> T) -> Result<T, &'static str> {
+    match scope {
+        KeyScope::Enclave => Ok(f(&*signing_key()?)),
+        KeyScope::Merchant(merchant) => Ok(f(&*merchant_key(merchant)?)),
+    }
+}
+
+fn os_seed(seed: &mut [u8; 32]) -> Result<(), &'static str> {
+    getrandom::getrandom(seed).map_err(|_| "rng_unavailable")
+}
+
+/// Install a fresh `scheme` key unless one is already installed. The seed
+/// is `fixed_seed` when given, so test deployments get a reproducible key
+/// (`OrdersConfig::signing_key_seed`), and the OS RNG otherwise.
+pub fn ensure_initialized(
+    scheme: SchemeKind,
+    fixed_seed: Option<&[u8; 32]>,
+) -> Result<(), &'static str> {
+    match fixed_seed {
+        Some(fixed) => ensure_initialized_with(scheme, |seed| {
+            *seed = *fixed;
+            warn!("Signing key derived from SIGNING_KEY_SEED_B64; not for production");
+            Ok(())
+        }),
+        None => ensure_initialized_with(scheme, os_seed),
+    }
+}
+
+/// Like `ensure_initialized`, drawing the seed from `fill_seed`. A no-op if
+/// a key is already installed. Tests substitute a failing or fixed source
+/// for the OS RNG here.
+pub fn ensure_initialized_with(
+    scheme: SchemeKind,
+    fill_seed: impl FnOnce(&mut [u8; 32]) -> Result<(), &'static str>,
+) -> Result<(), &'static str> {
+    let mut slot = SIGNING_KEY.write().expect("signing key lock poisoned");
+    if slot.is_some() {
+        return Ok(());
+    }
+    info!("🔧 Generating new signing key...");
+    let (seed, key) = generate(scheme, fill_seed)?;
+    let _ = MASTER_SEED.set(seed);
+    info!(
+        scheme = key.scheme().as_str(),
+        "✅ Signing key generated successfully"
+    );
+    *slot = Some(Arc::new(key));
+    Ok(())
+}
+
+/// A seed from `fill_seed` and the `scheme` key it derives.
+fn generate(
+    scheme: SchemeKind,
+    fill_seed: impl FnOnce(&mut [u8; 32]) -> Result<(), &'static str>,
+) -> Result<([u8; 32], EnclaveKey), &'static str> {
+    let mut seed = [0u8; 32];
+    fill_seed(&mut seed)?;
+    let key = EnclaveKey::from_seed_as(scheme, &seed)?;
+    Ok((seed, key))
+}
+
+/// Install a signing key whose 32-byte seed is sealed under an AWS KMS key
+/// and only released to an attested enclave. Must run before
+/// `ensure_initialized`; any failure is returned rather than falling back
+/// to a throwaway random key.
+pub async fn init_from_kms<K: KmsDecrypt>(
+    scheme: SchemeKind,
+    kms: &K,
+    ciphertext: &[u8],
+) -> Result<(), String> {
+    info!("🔧 Unsealing signing key via KMS...");
+    let (seed, key) = unseal(scheme, kms, ciphertext).await?;
+    let mut slot = SIGNING_KEY.write().expect("signing key lock poisoned");
+    if slot.is_some() {
+        return Err("signing key already initialized".to_string());
+    }
+    *slot = Some(Arc::new(key));
+    let _ = MASTER_SEED.set(seed);
+    info!("✅ Signing key unsealed successfully");
+    Ok(())
+}
+
+/// Decrypt `ciphertext` with `kms` into a seed and the `scheme` key it
+/// derives.
+async fn unseal<K: KmsDecrypt>(
+    scheme: SchemeKind,
+    kms: &K,
+    ciphertext: &[u8],
+) -> Result<([u8; 32], EnclaveKey), String> {
+    let plaintext = kms.decrypt(ciphertext).await?;
+    let seed: [u8; 32] = plaintext.as_slice().try_into().map_err(|_| {
+        format!(
+            "KMS plaintext is {} bytes, expected a 32-byte seed",
+            plaintext.len()
+        )
     })?;
+    let key =
+        EnclaveKey::from_seed_as(scheme, &seed).map_err(|e| format!("KMS seed rejected: {}", e))?;
+    Ok((seed, key))
+}
+
+/// Whether a signing key has been installed.
+pub fn is_initialized() -> bool {
+    SIGNING_KEY
+        .read()
+        .expect("signing key lock poisoned")
+        .is_some()
+}
+
+/// Public half of a key replaced by `rotate`.
+#[derive(Debug, Clone, Serialize)]
+pub struct RetiredKey {
+    pub kid: String,
+    pub public_key: String,
+    pub scheme: &'static str,
+    pub retired_at_ms: u64,
+}
+
+/// Outcome of `rotate`: the new key and the one it replaced.
+#[derive(Debug, Clone, Serialize)]
+pub struct RotatedKey {
+    pub kid: String,
+    pub public_key: String,
+    pub retired_kid: String,
+}
+
+/// Replace the enclave key with a fresh random one of the same scheme. The
+/// old public key joins the retired list, so signatures it made still
+/// verify until `MAX_RETIRED_KEYS` later rotations push it out. Merchant
+/// keys stay derived from the boot seed, and the attestation document
+/// commits to the new key from here on.
+pub fn rotate() -> Result<RotatedKey, &'static str> {
+    let mut seed = [0u8; 32];
+    os_seed(&mut seed)?;
+    let mut slot = SIGNING_KEY.write().expect("signing key lock poisoned");
+    let old = slot.clone().ok_or(KEY_UNINITIALIZED)?;
+    let new = EnclaveKey::from_seed_as(old.scheme(), &seed)?;
+    let rotated = RotatedKey {
+        kid: new.kid(),
+        public_key: B64.encode(new.public_key_bytes()),
+        retired_kid: old.kid(),
+    };
+    *slot = Some(Arc::new(new));
+    drop(slot);
+
+    retire(
+        &mut RETIRED_KEYS.lock().expect("retired keys lock poisoned"),
+        &old,
+    );
+    info!(kid = %rotated.kid, retired_kid = %rotated.retired_kid, "🔁 Signing key rotated");
+    Ok(rotated)
+}
+
+/// Add `old` to the `retired` ring, dropping the oldest past
+/// `MAX_RETIRED_KEYS`.
+fn retire(retired: &mut VecDeque<RetiredKey>, old: &EnclaveKey) {
+    retired.push_back(RetiredKey {
+        kid: old.kid(),
+        public_key: B64.encode(old.public_key_bytes()),
+        scheme: old.scheme().as_str(),
+        retired_at_ms: unix_time_ms(),
+    });
+    while retired.len() > MAX_RETIRED_KEYS {
+        retired.pop_front();
+    }
+}
+
+/// Base64 public key of the key in `retired` identified by `kid`.
+fn retired_public_key(retired: &VecDeque<RetiredKey>, kid: &str) -> Option<String> {
+    retired
+        .iter()
+        .find(|retired| retired.kid == kid)
+        .map(|retired| retired.public_key.clone())
+}
+
+/// Keys replaced by `rotate` that are still accepted for verification.
+pub fn retired_keys() -> Vec<RetiredKey> {
+    RETIRED_KEYS
+        .lock()
+        .expect("retired keys lock poisoned")
+        .iter()
+        .cloned()
+        .collect()
+}
+
+/// Hex of the first 8 bytes of SHA-256 over a raw public key.
+fn kid_of(public_key: &[u8]) -> String {
+    Hex::encode(&Sha256::digest(public_key).digest[..8])
+}
+
+/// `kid` of a base64 raw public key, as carried in `public_key`.
+pub fn kid_for_public_key(public_key_b64: &str) -> Result<String, &'static str> {
+    let bytes = B64
+        .decode(public_key_b64.trim())
+        .map_err(|_| "public_key_not_base64")?;
+    Ok(kid_of(&bytes))
+}
+
+/// Base64 public key of the key identified by `kid`: the current enclave
+/// key, a retired one, or a merchant key.
+pub fn public_key_for_kid(kid: &str) -> Option<String> {
+    if let Some(key) = signing_key().ok().filter(|key| key.kid() == kid) {
+        return Some(B64.encode(key.public_key_bytes()));
+    }
+    if let Some(public_key) = retired_public_key(
+        &RETIRED_KEYS.lock().expect("retired keys lock poisoned"),
+        kid,
+    ) {
+        return Some(public_key);
+    }
+    MERCHANT_KEYS
+        .read()
+        .expect("merchant keys lock poisoned")
+        .values()
+        .find(|key| key.kid() == kid)
+        .map(|key| B64.encode(key.public_key_bytes()))
+}
+
+/// Sign `rounds` throwaway messages so the first real orders do not pay for
+/// cold caches. Touches nothing but the key, and readiness reports the key
+/// unhealthy until it has run.
+pub fn warm_up(rounds: u32) -> Result<(), &'static str> {
+    let key = signing_key()?;
+    for round in 0..rounds {
+        let _ = key.sign(&round.to_le_bytes());
+    }
+    WARMED_UP.store(true, Ordering::Release);
+    info!(rounds, "Signing warmup complete");
     Ok(())
 }
 
-pub fn public_key_base64() -> String {
-    let sk = SIGNING_KEY
-        .get()
-        .expect("crypto::ensure_initialized must be called first");
-    let vk = sk.verifying_key();
-    B64.encode(vk.to_bytes())
+/// Whether `warm_up` has finished.
+pub fn is_warmed_up() -> bool {
+    WARMED_UP.load(Ordering::Acquire)
+}
+
+/// Scheme of the installed signing key.
+pub fn active_scheme() -> Result<SchemeKind, &'static str> {
+    Ok(signing_key()?.scheme())
+}
+
+/// Raw public key bytes, as committed to in the attestation document.
+pub fn public_key_bytes() -> Result<Vec<u8>, &'static str> {
+    Ok(signing_key()?.public_key_bytes())
+}
+
+pub fn public_key_base64() -> Result<String, &'static str> {
+    public_key_base64_in(KeyScope::Enclave)
+}
+
+pub fn public_key_base64_in(scope: KeyScope) -> Result<String, &'static str> {
+    with_key(scope, |key| B64.encode(key.public_key_bytes()))
+}
+
+/// Base64 public key of the key derived for `merchant`.
+pub fn public_key_for(merchant: &str) -> Result<String, &'static str> {
+    public_key_base64_in(KeyScope::Merchant(merchant))
+}
+
+/// Base64 public key of every merchant key, by merchant id.
+pub fn merchant_public_keys() -> BTreeMap<String, String> {
+    MERCHANT_KEYS
+        .read()
+        .expect("merchant keys lock poisoned")
+        .iter()
+        .map(|(merchant, key)| (merchant.clone(), B64.encode(key.public_key_bytes())))
+        .collect()
+}
+
+/// SHA-256 of `BCS(Vec<(merchant_id, raw_public_key)>)` over every merchant
+/// key in merchant id order, or `None` without merchant keys. The
+/// attestation document commits to it after the enclave key, so a verifier
+/// can check the keys listed by `/orders/health` against the attestation.
+pub fn merchant_keys_digest() -> Option<[u8; 32]> {
+    digest_of_merchant_keys(&MERCHANT_KEYS.read().expect("merchant keys lock poisoned"))
+}
+
+fn digest_of_merchant_keys(keys: &BTreeMap<String, Arc<EnclaveKey>>) -> Option<[u8; 32]> {
+    if keys.is_empty() {
+        return None;
+    }
+    let entries: Vec<(&str, Vec<u8>)> = keys
+        .iter()
+        .map(|(merchant, key)| (merchant.as_str(), key.public_key_bytes()))
+        .collect();
+    let encoded = bcs::to_bytes(&entries).expect("strings and bytes always encode");
+    Some(Sha256::digest(encoded).digest)
+}
+
+/// OID of Ed25519 public keys (RFC 8410).
+const OID_ED25519: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.3.101.112");
+/// OID of elliptic-curve public keys (RFC 5480).
+const OID_EC_PUBLIC_KEY: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.2.840.10045.2.1");
+/// OID of the secp256k1 curve (SEC 2).
+const OID_SECP256K1: ObjectIdentifier = ObjectIdentifier::new_unwrap("1.3.132.0.10");
+
+/// The enclave public key as a SubjectPublicKeyInfo PEM (`PUBLIC KEY`), as
+/// ops tooling and HSMs expect. secp256k1 keys keep their compressed point.
+pub fn public_key_pem() -> Result<String, &'static str> {
+    pem_of(&signing_key()?)
+}
+
+fn pem_of(key: &EnclaveKey) -> Result<String, &'static str> {
+    let algorithm = match key.scheme() {
+        SchemeKind::Ed25519 => AlgorithmIdentifierOwned {
+            oid: OID_ED25519,
+            parameters: None,
+        },
+        SchemeKind::Secp256k1 => {
+            let curve = Any::encode_from(&OID_SECP256K1).map_err(|_| "spki_encoding_failed")?;
+            AlgorithmIdentifierOwned {
+                oid: OID_EC_PUBLIC_KEY,
+                parameters: Some(curve),
+            }
+        }
+    };
+    let spki = SubjectPublicKeyInfoOwned {
+        algorithm,
+        subject_public_key: BitString::from_bytes(&key.public_key_bytes())
+            .map_err(|_| "spki_encoding_failed")?,
+    };
+    spki.to_pem(LineEnding::LF)
+        .map_err(|_| "spki_encoding_failed")
+}
+
+/// Short key fingerprint: hex of the first 8 bytes of SHA-256 over the raw
+/// public key. Lets clients pin and compare keys without storing them.
+pub fn public_key_fingerprint() -> Result<String, &'static str> {
+    public_key_fingerprint_in(KeyScope::Enclave)
+}
+
+pub fn public_key_fingerprint_in(scope: KeyScope) -> Result<String, &'static str> {
+    with_key(scope, |key| key.kid())
+}
+
+/// The verifying key as a JWK for JOSE libraries: an RFC 8037 OKP key for
+/// ed25519, an EC key for secp256k1. `kid` is the same fingerprint carried
+/// on signed responses.
+pub fn public_key_jwk() -> Result<Value, &'static str> {
+    Ok(signing_key()?.jwk())
+}
+
+/// Check a detached `signature` over `message` against a base64 public key.
+/// The scheme follows from the key length: 32 bytes is ed25519, 33 is a
+/// compressed secp256k1 point. `Ok(false)` means the signature does not
+/// verify; `Err` means the key itself is unusable.
+pub fn verify(
+    message: &[u8],
+    signature: &[u8; 64],
+    public_key_b64: &str,
+) -> Result<bool, &'static str> {
+    let key_bytes = B64
+        .decode(public_key_b64.trim())
+        .map_err(|_| "public_key_not_base64")?;
+    match key_bytes.len() {
+        32 => {
+            let key_bytes: [u8; 32] = key_bytes.try_into().expect("length checked");
+            let vk = VerifyingKey::from_bytes(&key_bytes).map_err(|_| "public_key_invalid")?;
+            Ok(vk
+                .verify(message, &Signature::from_bytes(signature))
+                .is_ok())
+        }
+        33 => {
+            let pk =
+                Secp256k1PublicKey::from_bytes(&key_bytes).map_err(|_| "public_key_invalid")?;
+            let Ok(sig) = Secp256k1Signature::from_bytes(signature) else {
+                return Ok(false);
+            };
+            Ok(pk.verify(message, &sig).is_ok())
+        }
+        _ => Err("public_key_wrong_length"),
+    }
+}
+
+pub fn sign(message: &[u8]) -> Result<[u8; 64], &'static str> {
+    sign_in(KeyScope::Enclave, message)
+}
+
+pub fn sign_in(scope: KeyScope, message: &[u8]) -> Result<[u8; 64], &'static str> {
+    let sig = with_key(scope, |key| key.sign(message))?;
+    info!("🔏 Signed {} byte message", message.len());
+    Ok(sig)
+}
+
+/// Message signed by `self_test`; never a valid signing message.
+const SELF_TEST_PROBE: &[u8] = b"nautilus/health/self-test";
+
+/// Sign a fixed probe with the enclave key and verify it against the
+/// key's own public half, so a key that exists but cannot produce valid
+/// signatures is caught.
+pub fn self_test() -> Result<(), &'static str> {
+    check_probe(&sign_with_key_in(KeyScope::Enclave, SELF_TEST_PROBE)?)
+}
+
+/// Whether `signed` is a valid signature of the self-test probe under the
+/// public key it names.
+fn check_probe(signed: &KeySignature) -> Result<(), &'static str> {
+    if verify(SELF_TEST_PROBE, &signed.signature, &signed.public_key)? {
+        Ok(())
+    } else {
+        Err("self_test_signature_mismatch")
+    }
 }
 
-pub fn sign(message: &[u8]) -> [u8; 64] {
-    let sk = SIGNING_KEY
-        .get()
-        .expect("crypto::ensure_initialized must be called first");
-    let sig = sk.sign(message);
+/// A signature together with the key that made it.
+pub struct KeySignature {
+    pub signature: [u8; 64],
+    pub public_key: String,
+    pub kid: String,
+    pub scheme: SchemeKind,
+}
+
+/// Like `sign_in`, also naming the key used. Both come from the same key
+/// even if `rotate` runs concurrently.
+pub fn sign_with_key_in(scope: KeyScope, message: &[u8]) -> Result<KeySignature, &'static str> {
+    let signed = with_key(scope, |key| KeySignature {
+        signature: key.sign(message),
+        public_key: B64.encode(key.public_key_bytes()),
+        kid: key.kid(),
+        scheme: key.scheme(),
+    })?;
     info!("🔏 Signed {} byte message", message.len());
-    sig.to_bytes()
+    Ok(signed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixed_seed(seed: &mut [u8; 32]) -> Result<(), &'static str> {
+        seed.fill(7);
+        Ok(())
+    }
+
+    fn failing_seed(_seed: &mut [u8; 32]) -> Result<(), &'static str> {
+        Err("rng_unavailable")
+    }
+
+    fn zero_seed(seed: &mut [u8; 32]) -> Result<(), &'static str> {
+        seed.fill(0);
+        Ok(())
+    }
+
+    #[test]
+    fn failing_seed_source_is_reported() {
+        assert_eq!(
+            generate(SchemeKind::Ed25519, failing_seed).err(),
+            Some("rng_unavailable")
+        );
+    }
+
+    #[test]
+    fn fixed_seed_source_gives_a_fixed_key() {
+        let (seed, key) = generate(SchemeKind::Ed25519, fixed_seed).unwrap();
+        assert_eq!(seed, [7; 32]);
+        let (_, again) = generate(SchemeKind::Ed25519, fixed_seed).unwrap();
+        assert_eq!(key.public_key_bytes(), again.public_key_bytes());
+    }
+
+    #[test]
+    fn zero_seed_is_not_a_secp256k1_key() {
+        assert_eq!(
+            generate(SchemeKind::Secp256k1, zero_seed).err(),
+            Some("seed_invalid")
+        );
+    }
+
+    /// Returns a fixed result for any ciphertext, standing in for KMS.
+    struct MockKms(Result<Vec<u8>, String>);
+
+    impl KmsDecrypt for MockKms {
+        async fn decrypt(&self, _ciphertext: &[u8]) -> Result<Vec<u8>, String> {
+            self.0.clone()
+        }
+    }
+
+    #[tokio::test]
+    async fn kms_seed_derives_the_same_key_as_that_seed() {
+        let kms = MockKms(Ok(vec![3; 32]));
+        let (seed, key) = unseal(SchemeKind::Ed25519, &kms, b"sealed").await.unwrap();
+        assert_eq!(seed, [3; 32]);
+        let expected = EnclaveKey::from_seed_as(SchemeKind::Ed25519, &[3; 32]).unwrap();
+        assert_eq!(key.public_key_bytes(), expected.public_key_bytes());
+    }
+
+    #[tokio::test]
+    async fn kms_failures_are_returned() {
+        let kms = MockKms(Err("AccessDenied".to_string()));
+        let err = unseal(SchemeKind::Ed25519, &kms, b"sealed").await.err();
+        assert_eq!(err.as_deref(), Some("AccessDenied"));
+        let kms = MockKms(Ok(vec![3; 31]));
+        assert!(unseal(SchemeKind::Ed25519, &kms, b"sealed").await.is_err());
+    }
+
+    fn merchants(ids: &[&str]) -> Vec<String> {
+        ids.iter().map(|id| id.to_string()).collect()
+    }
+
+    #[test]
+    fn merchant_keys_are_stable_and_distinct() {
+        ensure_initialized_with(SchemeKind::Ed25519, fixed_seed).unwrap();
+        derive_merchant_keys(&merchants(&["acme", "globex"])).unwrap();
+        let acme = public_key_for("acme").unwrap();
+        derive_merchant_keys(&merchants(&["acme"])).unwrap();
+        assert_eq!(public_key_for("acme").unwrap(), acme);
+        assert_ne!(public_key_for("globex").unwrap(), acme);
+        assert_ne!(public_key_base64().unwrap(), acme);
+    }
+
+    #[test]
+    fn merchants_off_the_allowlist_get_no_key() {
+        ensure_initialized_with(SchemeKind::Ed25519, fixed_seed).unwrap();
+        derive_merchant_keys(&merchants(&["initech"])).unwrap();
+        let signed = sign_with_key_in(KeyScope::Merchant("initech"), b"m").unwrap();
+        assert_eq!(public_key_for_kid(&signed.kid), Some(signed.public_key));
+        assert_eq!(
+            sign_with_key_in(KeyScope::Merchant("made-up"), b"m").err(),
+            Some("unknown_merchant")
+        );
+        assert!(!merchant_public_keys().contains_key("made-up"));
+        assert_eq!(public_key_for_kid("0000000000000000"), None);
+    }
+
+    #[test]
+    fn merchant_keys_digest_commits_to_every_key() {
+        let master = [7; 32];
+        let keys = |ids: &[&str]| -> BTreeMap<String, Arc<EnclaveKey>> {
+            ids.iter()
+                .map(|id| {
+                    let key = derive_merchant_key(&master, SchemeKind::Ed25519, id).unwrap();
+                    (id.to_string(), Arc::new(key))
+                })
+                .collect()
+        };
+        assert_eq!(digest_of_merchant_keys(&BTreeMap::new()), None);
+        let both = digest_of_merchant_keys(&keys(&["acme", "globex"])).unwrap();
+        assert_eq!(
+            digest_of_merchant_keys(&keys(&["globex", "acme"])),
+            Some(both)
+        );
+        assert_ne!(digest_of_merchant_keys(&keys(&["acme"])), Some(both));
+        let entries = vec![
+            ("acme", keys(&["acme"])["acme"].public_key_bytes()),
+            ("globex", keys(&["globex"])["globex"].public_key_bytes()),
+        ];
+        let expected = Sha256::digest(bcs::to_bytes(&entries).unwrap()).digest;
+        assert_eq!(both, expected);
+    }
+
+    #[test]
+    fn kid_of_a_public_key_matches_the_signing_kid() {
+        ensure_initialized_with(SchemeKind::Ed25519, fixed_seed).unwrap();
+        let signed = sign_with_key_in(KeyScope::Enclave, b"m").unwrap();
+        assert_eq!(kid_for_public_key(&signed.public_key).unwrap(), signed.kid);
+        assert!(kid_for_public_key("%%%").is_err());
+    }
+
+    fn jwk_coordinate(jwk: &Value, name: &str) -> Vec<u8> {
+        B64URL.decode(jwk[name].as_str().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn jwk_x_decodes_to_the_raw_key() {
+        let (_, key) = generate(SchemeKind::Ed25519, fixed_seed).unwrap();
+        let jwk = key.jwk();
+        assert_eq!(jwk["kty"], "OKP");
+        assert_eq!(jwk_coordinate(&jwk, "x"), key.public_key_bytes());
+        assert_eq!(jwk["kid"], key.kid());
+
+        let (_, key) = generate(SchemeKind::Secp256k1, fixed_seed).unwrap();
+        let jwk = key.jwk();
+        assert_eq!(jwk["kty"], "EC");
+        let (x, y) = (jwk_coordinate(&jwk, "x"), jwk_coordinate(&jwk, "y"));
+        // The compressed point is the parity of y followed by x.
+        let compressed = key.public_key_bytes();
+        assert_eq!(compressed[0], 2 + (y[31] & 1));
+        assert_eq!(&compressed[1..], &x[..]);
+    }
+
+    #[test]
+    fn known_seed_gives_the_known_public_key() {
+        let (_, key) = generate(SchemeKind::Ed25519, |seed| {
+            *seed = core::array::from_fn(|i| i as u8);
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(
+            B64.encode(key.public_key_bytes()),
+            "A6EHv/POEL4dcN0Y50vAmWfk1jCbpQ1fHdyGZBJVMbg="
+        );
+    }
+
+    #[test]
+    fn pem_parses_back_to_the_key_bytes() {
+        use spki::der::DecodePem;
+        for scheme in [SchemeKind::Ed25519, SchemeKind::Secp256k1] {
+            let (_, key) = generate(scheme, fixed_seed).unwrap();
+            let pem = pem_of(&key).unwrap();
+            let spki = SubjectPublicKeyInfoOwned::from_pem(&pem).unwrap();
+            assert_eq!(
+                spki.subject_public_key.raw_bytes(),
+                key.public_key_bytes().as_slice()
+            );
+        }
+        // Matches what OpenSSL-based tooling emits for the same key.
+        let (_, key) = generate(SchemeKind::Ed25519, fixed_seed).unwrap();
+        assert_eq!(
+            pem_of(&key).unwrap(),
+            "-----BEGIN PUBLIC KEY-----\n\
+             MCowBQYDK2VwAyEA6kpsY+KcUgq+9VB7Ey7F+ZVHdq6+vnuSQh7qaRRG0iw=\n\
+             -----END PUBLIC KEY-----\n"
+        );
+    }
+
+    #[test]
+    fn retired_keys_verify_until_pushed_out_of_the_ring() {
+        let (_, old) = generate(SchemeKind::Ed25519, fixed_seed).unwrap();
+        let signature = old.sign(b"signed before rotation");
+        let mut retired = VecDeque::new();
+        retire(&mut retired, &old);
+
+        let public_key = retired_public_key(&retired, &old.kid()).unwrap();
+        assert_eq!(
+            verify(b"signed before rotation", &signature, &public_key),
+            Ok(true)
+        );
+
+        // Only the last `MAX_RETIRED_KEYS` rotations stay verifiable.
+        let (_, later) = generate(SchemeKind::Ed25519, zero_seed).unwrap();
+        for _ in 0..MAX_RETIRED_KEYS {
+            retire(&mut retired, &later);
+        }
+        assert_eq!(retired.len(), MAX_RETIRED_KEYS);
+        assert_eq!(retired_public_key(&retired, &old.kid()), None);
+    }
+
+    #[test]
+    fn self_test_fails_when_the_key_halves_disagree() {
+        let (_, key) = generate(SchemeKind::Ed25519, fixed_seed).unwrap();
+        let (_, other) = generate(SchemeKind::Ed25519, zero_seed).unwrap();
+        let mut signed = KeySignature {
+            signature: key.sign(SELF_TEST_PROBE),
+            public_key: B64.encode(key.public_key_bytes()),
+            kid: key.kid(),
+            scheme: key.scheme(),
+        };
+        assert_eq!(check_probe(&signed), Ok(()));
+
+        signed.public_key = B64.encode(other.public_key_bytes());
+        assert_eq!(check_probe(&signed), Err("self_test_signature_mismatch"));
+    }
 }
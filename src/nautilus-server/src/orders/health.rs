@@ -0,0 +1,171 @@
+// Copyright (c), Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use axum::http::StatusCode;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use super::crypto;
+
+/// Readiness of the enclave or one of its subsystems. Ordered from best to
+/// worst so the overall status is simply the maximum over subsystems.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HealthStatus {
+    Healthy,
+    /// Still serving orders, but something an operator should look at.
+    Degraded,
+    Unhealthy,
+}
+
+impl HealthStatus {
+    /// Degraded still reports 200 so load balancers keep routing to us.
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            HealthStatus::Healthy | HealthStatus::Degraded => StatusCode::OK,
+            HealthStatus::Unhealthy => StatusCode::SERVICE_UNAVAILABLE,
+        }
+    }
+}
+
+/// Body of `GET /ready`.
+#[derive(Debug, Serialize)]
+pub struct ReadinessReport {
+    pub status: HealthStatus,
+    pub details: BTreeMap<&'static str, HealthStatus>,
+}
+
+impl ReadinessReport {
+    /// Fold per-subsystem states into a report whose overall status is the
+    /// worst of them.
+    pub fn from_checks(details: BTreeMap<&'static str, HealthStatus>) -> Self {
+        let status = details
+            .values()
+            .copied()
+            .max()
+            .unwrap_or(HealthStatus::Healthy);
+        Self { status, details }
+    }
+}
+
+/// Whether a background subsystem's last attempt succeeded. Cloned into
+/// the task doing the work, which flips it; `/ready` reports a failing one
+/// as degraded until a later attempt succeeds.
+#[derive(Debug, Clone, Default)]
+pub struct HealthFlag(Arc<AtomicBool>);
+
+impl HealthFlag {
+    pub fn fail(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn recover(&self) {
+        self.0.store(false, Ordering::Relaxed);
+    }
+
+    pub fn status(&self) -> HealthStatus {
+        if self.0.load(Ordering::Relaxed) {
+            HealthStatus::Degraded
+        } else {
+            HealthStatus::Healthy
+        }
+    }
+}
+
+/// Run the subsystem checks backing `/ready`. `audit_log` and `webhook` are
+/// the health of those subsystems when configured, and left out otherwise.
+pub fn check_subsystems(
+    audit_log: Option<&HealthFlag>,
+    webhook: Option<&HealthFlag>,
+) -> ReadinessReport {
+    let mut details = BTreeMap::new();
+    details.insert(
+        "signing_key",
+        if crypto::is_initialized() && crypto::is_warmed_up() {
+            HealthStatus::Healthy
+        } else {
+            HealthStatus::Unhealthy
+        },
+    );
+    // Without the nitro feature the enclave can sign but cannot attest to
+    // the key it signs with.
+    details.insert(
+        "attestation",
+        if cfg!(feature = "nitro") {
+            HealthStatus::Healthy
+        } else {
+            HealthStatus::Degraded
+        },
+    );
+    if let Some(flag) = audit_log {
+        details.insert("audit_log", flag.status());
+    }
+    if let Some(flag) = webhook {
+        details.insert("webhook", flag.status());
+    }
+    ReadinessReport::from_checks(details)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn overall_status_is_the_worst_subsystem() {
+        let report = ReadinessReport::from_checks(BTreeMap::from([
+            ("a", HealthStatus::Healthy),
+            ("b", HealthStatus::Degraded),
+        ]));
+        assert_eq!(report.status, HealthStatus::Degraded);
+        assert_eq!(report.status.status_code(), StatusCode::OK);
+        assert_eq!(
+            HealthStatus::Unhealthy.status_code(),
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+    }
+
+    #[test]
+    fn flag_degrades_until_recovered() {
+        let flag = HealthFlag::default();
+        let seen_by_task = flag.clone();
+        assert_eq!(flag.status(), HealthStatus::Healthy);
+        seen_by_task.fail();
+        assert_eq!(flag.status(), HealthStatus::Degraded);
+        seen_by_task.recover();
+        assert_eq!(flag.status(), HealthStatus::Healthy);
+    }
+
+    #[test]
+    fn failing_audit_and_webhook_show_in_details() {
+        let audit = HealthFlag::default();
+        let webhook = HealthFlag::default();
+        webhook.fail();
+        let report = check_subsystems(Some(&audit), Some(&webhook));
+        assert_eq!(report.details["audit_log"], HealthStatus::Healthy);
+        assert_eq!(report.details["webhook"], HealthStatus::Degraded);
+        assert!(report.status >= HealthStatus::Degraded);
+        let report = check_subsystems(None, None);
+        assert!(!report.details.contains_key("audit_log"));
+        assert!(!report.details.contains_key("webhook"));
+    }
+
+    fn fixed_seed(seed: &mut [u8; 32]) -> Result<(), &'static str> {
+        seed.fill(7);
+        Ok(())
+    }
+
+    #[test]
+    fn signing_key_is_ready_only_after_warmup() {
+        // The only test that warms up, so the key is still cold here.
+        crypto::ensure_initialized_with(crypto::SchemeKind::Ed25519, fixed_seed).unwrap();
+        let report = check_subsystems(None, None);
+        assert_eq!(report.details["signing_key"], HealthStatus::Unhealthy);
+        assert_eq!(report.status.status_code(), StatusCode::SERVICE_UNAVAILABLE);
+
+        crypto::warm_up(3).unwrap();
+        let report = check_subsystems(None, None);
+        assert_eq!(report.details["signing_key"], HealthStatus::Healthy);
+    }
+}
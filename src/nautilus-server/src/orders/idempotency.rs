@@ -0,0 +1,226 @@
+// Copyright (c), Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::EnclaveError;
+use axum::http::HeaderMap;
+use fastcrypto::hash::{HashFunction, Sha256};
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use super::{OrderRequest, SignedOrderResponse};
+
+/// Header naming a client's retry-safe key for `/orders/process`.
+pub const IDEMPOTENCY_KEY_HEADER: &str = "idempotency-key";
+
+/// Longest idempotency key accepted.
+const MAX_KEY_LEN: usize = 255;
+
+/// Rough footprint of one cached response.
+const ENTRY_BYTES: usize = 1280;
+
+struct Entry {
+    fingerprint: [u8; 32],
+    response: SignedOrderResponse,
+    stored_at: Instant,
+}
+
+/// Signed responses by `Idempotency-Key`, kept for `ttl` so a retried
+/// request gets the response it missed instead of a second signature.
+/// Two concurrent first requests with one key may both be processed; the
+/// order store and replay guard still stop them double-settling.
+pub struct IdempotencyCache {
+    ttl: Duration,
+    entries: Mutex<(HashMap<String, Entry>, VecDeque<String>)>,
+}
+
+impl IdempotencyCache {
+    pub fn new(ttl_ms: u64) -> Self {
+        Self {
+            ttl: Duration::from_millis(ttl_ms),
+            entries: Mutex::new((HashMap::new(), VecDeque::new())),
+        }
+    }
+
+    /// The cached response for `key`, if one is still live. The same key
+    /// with a different `fingerprint` is a client bug and refused with 422.
+    pub fn lookup(
+        &self,
+        key: &str,
+        fingerprint: &[u8; 32],
+    ) -> Result<Option<SignedOrderResponse>, EnclaveError> {
+        let mut guard = self
+            .entries
+            .lock()
+            .expect("idempotency cache lock poisoned");
+        self.evict_expired(&mut guard);
+        let Some(entry) = guard.0.get(key) else {
+            return Ok(None);
+        };
+        if &entry.fingerprint != fingerprint {
+            return Err(EnclaveError::ValidationFailed(vec![format!(
+                "{}: {:?} was already used with a different request",
+                IDEMPOTENCY_KEY_HEADER, key
+            )]));
+        }
+        Ok(Some(entry.response.clone()))
+    }
+
+    pub fn store(&self, key: String, fingerprint: [u8; 32], response: SignedOrderResponse) {
+        let mut guard = self
+            .entries
+            .lock()
+            .expect("idempotency cache lock poisoned");
+        self.evict_expired(&mut guard);
+        let (map, order) = &mut *guard;
+        if map.contains_key(&key) {
+            return;
+        }
+        order.push_back(key.clone());
+        map.insert(
+            key,
+            Entry {
+                fingerprint,
+                response,
+                stored_at: Instant::now(),
+            },
+        );
+    }
+
+    /// Entries are queued in insertion order, so expired ones sit in front.
+    fn evict_expired(&self, guard: &mut (HashMap<String, Entry>, VecDeque<String>)) {
+        let (map, order) = guard;
+        while let Some(key) = order.front() {
+            match map.get(key) {
+                Some(entry) if entry.stored_at.elapsed() < self.ttl => break,
+                _ => {
+                    map.remove(key);
+                    order.pop_front();
+                }
+            }
+        }
+    }
+
+    /// Approximate memory held by cached responses.
+    pub fn approx_bytes(&self) -> usize {
+        self.entries
+            .lock()
+            .expect("idempotency cache lock poisoned")
+            .0
+            .len()
+            * ENTRY_BYTES
+    }
+}
+
+/// The `Idempotency-Key` header, if sent. An empty, overlong or non-ASCII
+/// key is a 400.
+pub fn key_from_headers(headers: &HeaderMap) -> Result<Option<String>, EnclaveError> {
+    let Some(raw) = headers.get(IDEMPOTENCY_KEY_HEADER) else {
+        return Ok(None);
+    };
+    match raw.to_str() {
+        Ok(key) if !key.is_empty() && key.len() <= MAX_KEY_LEN => Ok(Some(key.to_string())),
+        _ => Err(EnclaveError::InvalidRequest {
+            code: "invalid_idempotency_key",
+            message: format!(
+                "{} must be 1 to {} visible ASCII characters",
+                IDEMPOTENCY_KEY_HEADER, MAX_KEY_LEN
+            ),
+        }),
+    }
+}
+
+/// Digest identifying a request for idempotency: its canonical JSON plus
+/// whether a COSE envelope was asked for.
+pub fn fingerprint(req: &OrderRequest, emit_cose: bool) -> Result<[u8; 32], EnclaveError> {
+    let mut bytes = serde_json::to_vec(req)
+        .map_err(|e| EnclaveError::GenericError(format!("JSON encoding failed: {}", e)))?;
+    bytes.push(emit_cose as u8);
+    Ok(Sha256::digest(&bytes).digest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::orders::{crypto, order};
+    use serde_json::json;
+
+    fn fixed_seed(seed: &mut [u8; 32]) -> Result<(), &'static str> {
+        seed.fill(7);
+        Ok(())
+    }
+
+    fn request(amount: u64) -> OrderRequest {
+        serde_json::from_value(json!({
+            "version": 1,
+            "order_id": "o1",
+            "customer": "alice",
+            "merchant": "acme",
+            "amount": amount,
+            "currency": "USD",
+            "action": "initiate",
+        }))
+        .unwrap()
+    }
+
+    fn signed(req: &OrderRequest) -> SignedOrderResponse {
+        crypto::ensure_initialized_with(crypto::SchemeKind::Ed25519, fixed_seed).unwrap();
+        let resp = order::process_order(req, None, None, None, 60_000).unwrap();
+        order::sign_response(&resp).unwrap()
+    }
+
+    #[test]
+    fn retry_with_the_same_body_gets_the_cached_response() {
+        let cache = IdempotencyCache::new(60_000);
+        let req = request(100);
+        let fingerprint = fingerprint(&req, false).unwrap();
+        assert!(cache.lookup("k1", &fingerprint).unwrap().is_none());
+        let first = signed(&req);
+        cache.store("k1".to_string(), fingerprint, first.clone());
+
+        let cached = cache.lookup("k1", &fingerprint).unwrap().unwrap();
+        assert_eq!(cached.signature, first.signature);
+        assert_eq!(cached.response.sequence, first.response.sequence);
+    }
+
+    #[test]
+    fn same_key_with_a_different_body_is_refused() {
+        let cache = IdempotencyCache::new(60_000);
+        let req = request(100);
+        cache.store(
+            "k1".to_string(),
+            fingerprint(&req, false).unwrap(),
+            signed(&req),
+        );
+        for other in [
+            fingerprint(&request(101), false).unwrap(),
+            fingerprint(&req, true).unwrap(),
+        ] {
+            let err = cache.lookup("k1", &other).unwrap_err();
+            assert_eq!(err.into_status_and_body().0.as_u16(), 422);
+        }
+    }
+
+    #[test]
+    fn entries_expire_after_the_ttl() {
+        let cache = IdempotencyCache::new(0);
+        let req = request(100);
+        let fingerprint = fingerprint(&req, false).unwrap();
+        cache.store("k1".to_string(), fingerprint, signed(&req));
+        assert!(cache.lookup("k1", &fingerprint).unwrap().is_none());
+        assert_eq!(cache.approx_bytes(), 0);
+    }
+
+    #[test]
+    fn malformed_keys_are_refused() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(key_from_headers(&headers).unwrap(), None);
+        headers.insert(IDEMPOTENCY_KEY_HEADER, "k1".parse().unwrap());
+        assert_eq!(key_from_headers(&headers).unwrap().as_deref(), Some("k1"));
+        headers.insert(IDEMPOTENCY_KEY_HEADER, "".parse().unwrap());
+        assert!(key_from_headers(&headers).is_err());
+        let long = "k".repeat(MAX_KEY_LEN + 1);
+        headers.insert(IDEMPOTENCY_KEY_HEADER, long.parse().unwrap());
+        assert!(key_from_headers(&headers).is_err());
+    }
+}
@@ -0,0 +1,153 @@
+// Copyright (c), Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use base64::engine::general_purpose::STANDARD as B64;
+use base64::Engine;
+use std::future::Future;
+use tokio::process::Command;
+use tracing::info;
+
+/// Decrypts a KMS ciphertext blob holding the sealed signing seed.
+/// Implementations are responsible for presenting the enclave's attestation
+/// document so KMS only releases the plaintext to an enclave whose PCRs
+/// satisfy the key policy.
+pub trait KmsDecrypt {
+    fn decrypt(&self, ciphertext: &[u8]) -> impl Future<Output = Result<Vec<u8>, String>> + Send;
+}
+
+/// `KmsDecrypt` backed by AWS's `kmstool_enclave_cli`. The tool fetches an
+/// attestation document from the NSM, sends it as the KMS `Recipient`, and
+/// unwraps the response with a key that never leaves the enclave, so the
+/// seed is only ever visible in plaintext inside this enclave.
+pub struct KmstoolCli {
+    pub binary: String,
+    pub region: String,
+    /// KMS key the ciphertext must have been encrypted under, passed as
+    /// `--key-id`. `None` lets KMS pick it from the ciphertext.
+    pub key_id: Option<String>,
+    /// vsock-proxy port on the parent instance forwarding to KMS.
+    pub proxy_port: u16,
+    pub access_key_id: String,
+    pub secret_access_key: String,
+    pub session_token: String,
+}
+
+impl KmstoolCli {
+    /// Build for the tool at `binary` and the proxy on `proxy_port`
+    /// (`OrdersConfig::kmstool_path` and `kms_proxy_port`), with the AWS
+    /// credentials passed in through the secrets blob (see run.sh).
+    pub fn from_env(binary: &str, proxy_port: u16) -> Result<Self, String> {
+        let required =
+            |name: &str| std::env::var(name).map_err(|_| format!("{} must be set", name));
+        Ok(Self {
+            binary: binary.to_string(),
+            region: required("AWS_REGION")?,
+            key_id: None,
+            proxy_port,
+            access_key_id: required("AWS_ACCESS_KEY_ID")?,
+            secret_access_key: required("AWS_SECRET_ACCESS_KEY")?,
+            session_token: required("AWS_SESSION_TOKEN")?,
+        })
+    }
+
+    /// Arguments for a `kmstool_enclave_cli decrypt` of `ciphertext`.
+    fn args(&self, ciphertext: &[u8]) -> Vec<String> {
+        let mut args = vec![
+            "decrypt".to_string(),
+            "--region".to_string(),
+            self.region.clone(),
+            "--proxy-port".to_string(),
+            self.proxy_port.to_string(),
+            "--aws-access-key-id".to_string(),
+            self.access_key_id.clone(),
+            "--aws-secret-access-key".to_string(),
+            self.secret_access_key.clone(),
+            "--aws-session-token".to_string(),
+            self.session_token.clone(),
+        ];
+        if let Some(key_id) = &self.key_id {
+            args.extend(["--key-id".to_string(), key_id.clone()]);
+        }
+        args.extend(["--ciphertext".to_string(), B64.encode(ciphertext)]);
+        args
+    }
+}
+
+impl KmsDecrypt for KmstoolCli {
+    async fn decrypt(&self, ciphertext: &[u8]) -> Result<Vec<u8>, String> {
+        info!(
+            region = %self.region,
+            key_id = self.key_id.as_deref().unwrap_or("-"),
+            proxy_port = self.proxy_port,
+            "Requesting attested KMS decrypt"
+        );
+        let output = Command::new(&self.binary)
+            .args(self.args(ciphertext))
+            .output()
+            .await
+            .map_err(|e| format!("failed to run {}: {}", self.binary, e))?;
+
+        if !output.status.success() {
+            return Err(format!(
+                "kmstool decrypt failed ({}): {}",
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+
+        parse_plaintext(&String::from_utf8_lossy(&output.stdout))
+    }
+}
+
+/// The decoded `PLAINTEXT:` line of kmstool's output.
+fn parse_plaintext(stdout: &str) -> Result<Vec<u8>, String> {
+    let plaintext_b64 = stdout
+        .lines()
+        .find_map(|line| line.strip_prefix("PLAINTEXT:"))
+        .map(str::trim)
+        .ok_or_else(|| "kmstool output has no PLAINTEXT line".to_string())?;
+    B64.decode(plaintext_b64)
+        .map_err(|e| format!("kmstool plaintext is not base64: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kmstool(key_id: Option<&str>) -> KmstoolCli {
+        KmstoolCli {
+            binary: "/kmstool_enclave_cli".to_string(),
+            region: "us-east-1".to_string(),
+            key_id: key_id.map(str::to_string),
+            proxy_port: 8000,
+            access_key_id: "AKID".to_string(),
+            secret_access_key: "SECRET".to_string(),
+            session_token: "TOKEN".to_string(),
+        }
+    }
+
+    #[test]
+    fn key_arn_is_passed_to_the_attested_decrypt() {
+        let arn = "arn:aws:kms:us-east-1:111122223333:key/abcd";
+        let args = kmstool(Some(arn)).args(b"sealed");
+        let at = args.iter().position(|a| a == "--key-id").unwrap();
+        assert_eq!(args[at + 1], arn);
+        assert_eq!(args.last().unwrap(), &B64.encode(b"sealed"));
+    }
+
+    #[test]
+    fn key_id_is_omitted_when_unset() {
+        assert!(!kmstool(None)
+            .args(b"sealed")
+            .iter()
+            .any(|a| a == "--key-id"));
+    }
+
+    #[test]
+    fn plaintext_line_is_decoded() {
+        let stdout = format!("STATUS: ok\nPLAINTEXT: {}\n", B64.encode([7u8; 32]));
+        assert_eq!(parse_plaintext(&stdout).unwrap(), vec![7u8; 32]);
+        assert!(parse_plaintext("STATUS: ok\n").is_err());
+        assert!(parse_plaintext("PLAINTEXT: %%%\n").is_err());
+    }
+}
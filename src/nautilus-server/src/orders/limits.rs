@@ -0,0 +1,310 @@
+// Copyright (c), Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::EnclaveError;
+
+use super::OrderAction;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::Mutex;
+
+/// Number of tracked (customer, currency) pairs above which a record call
+/// also sweeps windows that have fully expired for other customers.
+const SWEEP_THRESHOLD: usize = 4096;
+
+/// Rough footprint of one tracked (customer, currency) window: the key
+/// strings, the map slot and a handful of queued records.
+const SPENDING_ENTRY_BYTES: usize = 256;
+
+/// Rough footprint of one replay-guard entry (set slot plus queue slot).
+pub const REPLAY_ENTRY_BYTES: usize = 128;
+
+/// Rolling per-customer spend caps. Every `Initiate` adds its amount to the
+/// customer's window for that currency; an order that would push the sum
+/// within the last `window_ms` past the currency's cap is refused before it
+/// is signed. Currencies without a configured cap are not tracked.
+pub struct SpendingWindows {
+    window_ms: u64,
+    caps: HashMap<String, u64>,
+    entries: Mutex<HashMap<(String, String), VecDeque<(u64, u64)>>>,
+}
+
+impl SpendingWindows {
+    pub fn new(window_ms: u64, caps: HashMap<String, u64>) -> Self {
+        Self {
+            window_ms,
+            caps,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Record `amount` for `customer` at `now_ms`, or refuse with
+    /// `customer_limit_exceeded` if it would exceed the currency cap.
+    pub fn try_record(
+        &self,
+        customer: &str,
+        currency: &str,
+        amount: u64,
+        now_ms: u64,
+    ) -> Result<(), EnclaveError> {
+        let Some(&cap) = self.caps.get(currency) else {
+            return Ok(());
+        };
+        let cutoff = now_ms.saturating_sub(self.window_ms);
+        let mut entries = self.entries.lock().expect("spending windows lock poisoned");
+
+        if entries.len() > SWEEP_THRESHOLD {
+            entries.retain(|_, window| window.back().is_some_and(|&(ts, _)| ts > cutoff));
+        }
+
+        let window = entries
+            .entry((customer.to_string(), currency.to_string()))
+            .or_default();
+        while window.front().is_some_and(|&(ts, _)| ts <= cutoff) {
+            window.pop_front();
+        }
+
+        let spent = window
+            .iter()
+            .fold(0u64, |sum, &(_, amt)| sum.saturating_add(amt));
+        if spent.saturating_add(amount) > cap {
+            return Err(EnclaveError::InvalidRequest {
+                code: "customer_limit_exceeded",
+                message: format!(
+                    "customer would exceed the {} {} limit for the current window",
+                    cap, currency
+                ),
+            });
+        }
+        window.push_back((now_ms, amount));
+        Ok(())
+    }
+
+    /// Drop the record `try_record` made for `amount` at `now_ms`, for an
+    /// order that was not signed after all.
+    pub fn forget(&self, customer: &str, currency: &str, amount: u64, now_ms: u64) {
+        let mut entries = self.entries.lock().expect("spending windows lock poisoned");
+        let Some(window) = entries.get_mut(&(customer.to_string(), currency.to_string())) else {
+            return;
+        };
+        if let Some(pos) = window
+            .iter()
+            .rposition(|&record| record == (now_ms, amount))
+        {
+            window.remove(pos);
+        }
+    }
+
+    /// Approximate memory held by the tracked windows.
+    pub fn approx_bytes(&self) -> usize {
+        self.entries
+            .lock()
+            .expect("spending windows lock poisoned")
+            .len()
+            * SPENDING_ENTRY_BYTES
+    }
+}
+
+/// A settling action on an order, keyed by the escrowed balance it started
+/// from so successive installments are distinct but a replayed one is not.
+type ReplayKey = (String, OrderAction, Option<u64>);
+
+/// Remembers which settling actions (`Release`/`Refund`) have been signed,
+/// per order and starting balance, so replaying a request cannot mint a
+/// second signature for the same settlement or installment. Bounded: past
+/// `capacity` entries the oldest is forgotten.
+pub struct ReplayGuard {
+    capacity: usize,
+    seen: Mutex<(HashSet<ReplayKey>, VecDeque<ReplayKey>)>,
+}
+
+impl ReplayGuard {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            seen: Mutex::new((HashSet::new(), VecDeque::new())),
+        }
+    }
+
+    /// Record `action` on `order_id` from escrowed balance `remaining_before`,
+    /// or refuse with 409 `replayed_action` if it was already signed. Other
+    /// actions always pass.
+    pub fn try_record(
+        &self,
+        order_id: &str,
+        action: OrderAction,
+        remaining_before: Option<u64>,
+    ) -> Result<(), EnclaveError> {
+        if !matches!(action, OrderAction::Release | OrderAction::Refund) {
+            return Ok(());
+        }
+        let mut guard = self.seen.lock().expect("replay guard lock poisoned");
+        let (set, order) = &mut *guard;
+        let key = (order_id.to_string(), action, remaining_before);
+        if set.contains(&key) {
+            return Err(EnclaveError::Conflict {
+                code: "replayed_action",
+                message: format!(
+                    "{:?} from this balance was already signed for order {}",
+                    action, order_id
+                ),
+            });
+        }
+        if order.len() >= self.capacity {
+            if let Some(oldest) = order.pop_front() {
+                set.remove(&oldest);
+            }
+        }
+        set.insert(key.clone());
+        order.push_back(key);
+        Ok(())
+    }
+
+    /// Forget `action` on `order_id` after `try_record` accepted it, for a
+    /// settlement that was not signed after all, so a retry is not refused
+    /// as a replay.
+    pub fn forget(&self, order_id: &str, action: OrderAction, remaining_before: Option<u64>) {
+        let mut guard = self.seen.lock().expect("replay guard lock poisoned");
+        let (set, order) = &mut *guard;
+        let key = (order_id.to_string(), action, remaining_before);
+        if set.remove(&key) {
+            order.retain(|seen| seen != &key);
+        }
+    }
+
+    /// Approximate memory held by the guard.
+    pub fn approx_bytes(&self) -> usize {
+        self.seen
+            .lock()
+            .expect("replay guard lock poisoned")
+            .1
+            .len()
+            * REPLAY_ENTRY_BYTES
+    }
+}
+
+/// Refuse with 503 `state_capacity` once the in-memory stores together hold
+/// about `budget_bytes`. Only requests that may add state should call this;
+/// lookups and health checks keep working while it sheds load.
+pub fn check_state_budget(used_bytes: usize, budget_bytes: usize) -> Result<(), EnclaveError> {
+    if used_bytes >= budget_bytes {
+        return Err(EnclaveError::Unavailable {
+            code: "state_capacity",
+            message: "enclave state is at capacity, retry later".to_string(),
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replay_guard_refuses_a_second_settlement() {
+        let guard = ReplayGuard::new(16);
+        guard
+            .try_record("o1", OrderAction::Release, Some(100))
+            .unwrap();
+        let err = guard
+            .try_record("o1", OrderAction::Release, Some(100))
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            EnclaveError::Conflict {
+                code: "replayed_action",
+                ..
+            }
+        ));
+        // Non-settling actions are never guarded.
+        guard.try_record("o1", OrderAction::Deposit, None).unwrap();
+        guard.try_record("o1", OrderAction::Deposit, None).unwrap();
+    }
+
+    #[test]
+    fn replay_guard_exempts_initiate() {
+        let guard = ReplayGuard::new(16);
+        for _ in 0..3 {
+            guard.try_record("o1", OrderAction::Initiate, None).unwrap();
+        }
+        assert_eq!(guard.approx_bytes(), 0);
+    }
+
+    #[test]
+    fn replay_guard_evicts_the_oldest_past_capacity() {
+        let guard = ReplayGuard::new(2);
+        for order_id in ["o1", "o2", "o3"] {
+            guard
+                .try_record(order_id, OrderAction::Refund, Some(5))
+                .unwrap();
+        }
+        assert_eq!(guard.approx_bytes(), 2 * REPLAY_ENTRY_BYTES);
+        guard
+            .try_record("o1", OrderAction::Refund, Some(5))
+            .unwrap();
+        assert!(guard
+            .try_record("o3", OrderAction::Refund, Some(5))
+            .is_err());
+    }
+
+    #[test]
+    fn replay_guard_tells_installments_apart() {
+        let guard = ReplayGuard::new(16);
+        guard
+            .try_record("o1", OrderAction::Release, Some(100))
+            .unwrap();
+        guard
+            .try_record("o1", OrderAction::Release, Some(60))
+            .unwrap();
+        assert!(guard
+            .try_record("o1", OrderAction::Release, Some(60))
+            .is_err());
+    }
+
+    #[test]
+    fn replay_guard_forget_allows_a_retry() {
+        let guard = ReplayGuard::new(16);
+        guard
+            .try_record("o1", OrderAction::Refund, Some(5))
+            .unwrap();
+        guard.forget("o1", OrderAction::Refund, Some(5));
+        guard
+            .try_record("o1", OrderAction::Refund, Some(5))
+            .unwrap();
+        assert_eq!(guard.approx_bytes(), REPLAY_ENTRY_BYTES);
+    }
+
+    #[test]
+    fn customer_hits_the_cap_then_recovers_after_the_window() {
+        let windows = SpendingWindows::new(1_000, HashMap::from([("USD".to_string(), 100)]));
+        for ts in [0, 100, 200, 300] {
+            windows.try_record("alice", "USD", 25, ts).unwrap();
+        }
+        assert!(matches!(
+            windows.try_record("alice", "USD", 1, 400),
+            Err(EnclaveError::InvalidRequest {
+                code: "customer_limit_exceeded",
+                ..
+            })
+        ));
+        // Other customers and uncapped currencies are unaffected.
+        windows.try_record("bob", "USD", 100, 400).unwrap();
+        windows.try_record("alice", "EUR", 1_000, 400).unwrap();
+        // The first record leaves the window once it is `window_ms` old.
+        windows.try_record("alice", "USD", 25, 1_000).unwrap();
+    }
+
+    #[test]
+    fn spending_windows_forget_frees_the_amount() {
+        let windows = SpendingWindows::new(1_000, HashMap::from([("USD".to_string(), 100)]));
+        windows.try_record("alice", "USD", 80, 10).unwrap();
+        assert!(windows.try_record("alice", "USD", 30, 11).is_err());
+        windows.forget("alice", "USD", 80, 10);
+        windows.try_record("alice", "USD", 30, 11).unwrap();
+    }
+
+    #[test]
+    fn state_budget_sheds_at_capacity() {
+        assert!(check_state_budget(99, 100).is_ok());
+        assert!(check_state_budget(100, 100).is_err());
+    }
+}
@@ -0,0 +1,299 @@
+// Copyright (c), Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::EnclaveError;
+use serde_json::Value;
+
+/// Top-level keys a client may not use in `metadata`. These are the field
+/// names of `SignableOrderResponse` / `SignedOrderResponse` plus names
+/// reserved for server-computed fields, so client data can never be
+/// mistaken for a server-authoritative value.
+pub const RESERVED_METADATA_KEYS: &[&str] = &[
+    // SignableOrderResponse
+    "field_version",
+    "version",
+    "order_id",
+    "action",
+    "status",
+    "amount",
+    "currency",
+    "server_timestamp_ms",
+    "escrow_tx_id",
+    "notes",
+    "client_reference",
+    "sequence",
+    "expires_at_ms",
+    "remaining_amount",
+    "request_digest",
+    "fee_amount",
+    "net_amount",
+    "metadata_digest",
+    "settle_amount",
+    // SignedOrderResponse
+    "response",
+    "signature",
+    "public_key",
+    "public_key_fingerprint",
+    "kid",
+    "digest",
+    "scheme",
+    "alg",
+    "signature_v2",
+    "v2",
+    "cose_sign1",
+    "operator_signature",
+    // Reserved for server-computed fields
+    "server_epoch",
+];
+
+/// Reject metadata whose top-level object contains a reserved key.
+/// Non-object metadata has no keys and always passes.
+pub fn check_reserved_keys(metadata: Option<&Value>) -> Result<(), EnclaveError> {
+    let Some(Value::Object(map)) = metadata else {
+        return Ok(());
+    };
+    if let Some(key) = map
+        .keys()
+        .find(|k| RESERVED_METADATA_KEYS.contains(&k.as_str()))
+    {
+        return Err(EnclaveError::InvalidRequest {
+            code: "reserved_metadata_key",
+            message: format!("metadata key {:?} is reserved for server fields", key),
+        });
+    }
+    Ok(())
+}
+
+/// Reject metadata holding more than `max` elements in total, counting every
+/// array element and object entry at every nesting level. A flat array of
+/// tiny values is cheap for a client to send but costly to hold and
+/// re-serialize.
+pub fn check_element_count(metadata: Option<&Value>, max: usize) -> Result<(), EnclaveError> {
+    let Some(value) = metadata else {
+        return Ok(());
+    };
+    let mut count = 0usize;
+    if !count_elements(value, max, &mut count) {
+        return Err(EnclaveError::InvalidRequest {
+            code: "metadata_too_many_elements",
+            message: format!("metadata exceeds the limit of {} elements", max),
+        });
+    }
+    Ok(())
+}
+
+/// Add the elements under `value` to `count`, stopping as soon as it passes
+/// `max`. Returns `false` once the limit is exceeded.
+fn count_elements(value: &Value, max: usize, count: &mut usize) -> bool {
+    let children: Box<dyn Iterator<Item = &Value>> = match value {
+        Value::Array(items) => Box::new(items.iter()),
+        Value::Object(map) => Box::new(map.values()),
+        _ => return true,
+    };
+    for child in children {
+        *count += 1;
+        if *count > max || !count_elements(child, max, count) {
+            return false;
+        }
+    }
+    true
+}
+
+/// Reject metadata nested more than `max` arrays/objects deep with a 422
+/// giving the limit. Descends at most `max + 1` levels, so hostile input cannot exhaust the
+/// stack here or later when the metadata is re-serialized.
+pub fn check_depth(metadata: Option<&Value>, max: usize) -> Result<(), EnclaveError> {
+    let Some(value) = metadata else {
+        return Ok(());
+    };
+    if exceeds_depth(value, max) {
+        return Err(EnclaveError::ValidationFailed(vec![format!(
+            "metadata: nested deeper than {} levels",
+            max
+        )]));
+    }
+    Ok(())
+}
+
+/// Whether `value` nests more than `max` arrays/objects deep.
+fn exceeds_depth(value: &Value, max: usize) -> bool {
+    let mut children: Box<dyn Iterator<Item = &Value>> = match value {
+        Value::Array(items) => Box::new(items.iter()),
+        Value::Object(map) => Box::new(map.values()),
+        _ => return false,
+    };
+    let Some(remaining) = max.checked_sub(1) else {
+        return true;
+    };
+    children.any(|child| exceeds_depth(child, remaining))
+}
+
+/// Reject metadata whose compact JSON encoding is over `max` bytes with a
+/// 422 giving the size and limit. Run after `check_depth`, which bounds the serializer's recursion.
+pub fn check_size(metadata: Option<&Value>, max: usize) -> Result<(), EnclaveError> {
+    let Some(value) = metadata else {
+        return Ok(());
+    };
+    let len = serde_json::to_vec(value)
+        .map_err(|e| EnclaveError::GenericError(format!("metadata is not encodable: {}", e)))?
+        .len();
+    if len > max {
+        return Err(EnclaveError::ValidationFailed(vec![format!(
+            "metadata: {} bytes serialized, limit is {}",
+            len, max
+        )]));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn code(result: Result<(), EnclaveError>) -> &'static str {
+        match result {
+            Err(EnclaveError::InvalidRequest { code, .. }) => code,
+            other => panic!("expected InvalidRequest, got {:?}", other),
+        }
+    }
+
+    fn reason(result: Result<(), EnclaveError>) -> String {
+        match result {
+            Err(EnclaveError::ValidationFailed(mut fields)) if fields.len() == 1 => {
+                fields.remove(0)
+            }
+            other => panic!("expected ValidationFailed, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn reserved_top_level_keys_are_refused() {
+        let metadata = json!({ "amount": 1 });
+        assert_eq!(
+            code(check_reserved_keys(Some(&metadata))),
+            "reserved_metadata_key"
+        );
+        let nested = json!({ "note": { "amount": 1 } });
+        assert!(check_reserved_keys(Some(&nested)).is_ok());
+        assert!(check_reserved_keys(Some(&json!(["amount"]))).is_ok());
+    }
+
+    #[test]
+    fn sequence_is_reserved() {
+        let metadata = json!({ "sequence": 7, "note": "hi" });
+        assert_eq!(
+            code(check_reserved_keys(Some(&metadata))),
+            "reserved_metadata_key"
+        );
+    }
+
+    #[test]
+    fn every_signed_field_is_reserved() {
+        let resp = serde_json::to_value(crate::orders::SignableOrderResponse {
+            field_version: 0,
+            version: 1,
+            order_id: String::new(),
+            action: crate::orders::OrderAction::Initiate,
+            status: crate::orders::OrderStatus::Pending,
+            amount: 0,
+            currency: String::new(),
+            server_timestamp_ms: 0,
+            escrow_tx_id: None,
+            notes: None,
+            client_reference: None,
+            sequence: 0,
+            expires_at_ms: None,
+            remaining_amount: None,
+            request_digest: String::new(),
+            fee_amount: 0,
+            net_amount: 0,
+            metadata_digest: Some(String::new()),
+            settle_amount: None,
+        })
+        .unwrap();
+        for key in resp.as_object().unwrap().keys() {
+            assert!(RESERVED_METADATA_KEYS.contains(&key.as_str()), "{}", key);
+        }
+    }
+
+    #[test]
+    fn element_count_includes_nested_entries() {
+        let metadata = json!({ "a": [1, 2], "b": { "c": 3 } });
+        assert!(check_element_count(Some(&metadata), 5).is_ok());
+        assert_eq!(
+            code(check_element_count(Some(&metadata), 4)),
+            "metadata_too_many_elements"
+        );
+    }
+
+    #[test]
+    fn large_flat_array_is_refused() {
+        let metadata = serde_json::Value::Array(vec![json!(0); 257]);
+        assert_eq!(
+            code(check_element_count(Some(&metadata), 256)),
+            "metadata_too_many_elements"
+        );
+        let metadata = serde_json::Value::Array(vec![json!(0); 256]);
+        assert!(check_element_count(Some(&metadata), 256).is_ok());
+    }
+
+    #[test]
+    fn depth_counts_arrays_and_objects() {
+        let metadata = json!({ "a": [{ "b": 1 }] });
+        assert!(check_depth(Some(&metadata), 3).is_ok());
+        assert_eq!(
+            reason(check_depth(Some(&metadata), 2)),
+            "metadata: nested deeper than 2 levels"
+        );
+        assert!(check_depth(Some(&json!(1)), 0).is_ok());
+    }
+
+    #[test]
+    fn size_is_the_compact_encoding() {
+        let metadata = json!({ "k": "v" });
+        assert!(check_size(Some(&metadata), 9).is_ok());
+        assert_eq!(
+            reason(check_size(Some(&metadata), 8)),
+            "metadata: 9 bytes serialized, limit is 8"
+        );
+    }
+
+    #[test]
+    fn too_deep_and_too_large_are_unprocessable() {
+        let metadata = json!({ "a": [[[[1]]]] });
+        for result in [
+            check_depth(Some(&metadata), 1),
+            check_size(Some(&metadata), 1),
+        ] {
+            let (status, body) = result.unwrap_err().into_status_and_body();
+            assert_eq!(status, axum::http::StatusCode::UNPROCESSABLE_ENTITY);
+            assert_eq!(body["code"], "validation_failed");
+            assert_eq!(body["details"].as_array().unwrap().len(), 1);
+        }
+    }
+
+    #[test]
+    fn oversized_blob_is_refused_at_the_default_limit() {
+        let max = crate::orders::OrdersConfig::default().max_metadata_bytes;
+        // `{"k":""}` is 8 bytes around the string.
+        let fits = json!({ "k": "x".repeat(max - 8) });
+        assert!(check_size(Some(&fits), max).is_ok());
+        let over = json!({ "k": "x".repeat(max - 7) });
+        assert_eq!(
+            reason(check_size(Some(&over), max)),
+            format!("metadata: {} bytes serialized, limit is {}", max + 1, max)
+        );
+    }
+
+    #[test]
+    fn deeply_nested_blob_is_refused_at_the_default_limit() {
+        let max = crate::orders::OrdersConfig::default().max_metadata_depth;
+        let nested = |depth| (0..depth).fold(json!(1), |inner, _| json!([inner]));
+        assert!(check_depth(Some(&nested(max)), max).is_ok());
+        assert_eq!(
+            reason(check_depth(Some(&nested(max + 1)), max)),
+            format!("metadata: nested deeper than {} levels", max)
+        );
+    }
+}
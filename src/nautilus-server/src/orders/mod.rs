@@ -3,12 +3,31 @@
 
 #![cfg(feature = "orders")]
 
+pub mod admin;
+pub mod audit;
+pub mod config;
+pub mod cose;
+pub mod cosign;
 pub mod crypto;
+pub mod health;
+pub mod idempotency;
+pub mod kms;
+pub mod limits;
+pub mod metadata;
+pub mod money;
+pub mod naming;
+pub mod negotiate;
 pub mod order;
+pub mod routes;
+pub mod store;
+pub mod verification;
+pub mod webhook;
 
 // Re-export for convenience
+pub use config::OrdersConfig;
 pub use crypto::{ensure_initialized, public_key_base64, sign};
 pub use order::{
-    make_response, sign_response, sign_response_with_v2, OrderAction, OrderRequest, OrderStatus,
-    OrderV2Fields, SignableOrderResponse, SignedOrderResponse,
+    make_response, process_order, sign_response, sign_response_with_v2, signing_message,
+    OrderAction, OrderRequest, OrderStatus, OrderV2Fields, SignableOrderResponse,
+    SignedOrderResponse, SigningFormat,
 };
@@ -0,0 +1,145 @@
+// Copyright (c), Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::fmt;
+use std::str::FromStr;
+
+/// An amount in minor units. Arithmetic is checked and fails with a reason
+/// suitable for a `Rejected` response's notes instead of wrapping or
+/// panicking.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub struct Money(u64);
+
+impl Money {
+    pub const fn new(minor_units: u64) -> Self {
+        Money(minor_units)
+    }
+
+    pub const fn get(self) -> u64 {
+        self.0
+    }
+
+    pub fn is_zero(self) -> bool {
+        self.0 == 0
+    }
+
+    pub fn add(self, other: Money) -> Result<Money, String> {
+        self.0
+            .checked_add(other.0)
+            .map(Money)
+            .ok_or_else(|| format!("adding {} to {} overflows", other, self))
+    }
+
+    pub fn sub(self, other: Money) -> Result<Money, String> {
+        self.0
+            .checked_sub(other.0)
+            .map(Money)
+            .ok_or_else(|| format!("{} exceeds the balance of {}", other, self))
+    }
+
+    /// Split into `(fee, net)` at `rate`. The fee rounds down, so
+    /// `fee + net` is always exactly `self`.
+    pub fn split_fee(self, rate: BasisPoints) -> (Money, Money) {
+        // Below 2^64 * 10_000, so the product cannot overflow u128, and the
+        // quotient is at most `self`.
+        let fee = (self.0 as u128 * rate.0 as u128 / BasisPoints::MAX.0 as u128) as u64;
+        (Money(fee), Money(self.0 - fee))
+    }
+}
+
+/// A rate in hundredths of a percent, from 0 to 10_000 (100%).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub struct BasisPoints(u16);
+
+impl BasisPoints {
+    pub const MAX: BasisPoints = BasisPoints(10_000);
+
+    pub fn new(bps: u16) -> Option<Self> {
+        (bps <= Self::MAX.0).then_some(BasisPoints(bps))
+    }
+
+    pub const fn get(self) -> u16 {
+        self.0
+    }
+}
+
+impl FromStr for BasisPoints {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.parse::<u16>()
+            .ok()
+            .and_then(BasisPoints::new)
+            .ok_or_else(|| format!("{:?} is not a rate between 0 and 10000 bps", s))
+    }
+}
+
+impl From<u64> for Money {
+    fn from(minor_units: u64) -> Self {
+        Money(minor_units)
+    }
+}
+
+impl fmt::Display for Money {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn adding_past_u64_max_fails() {
+        let max = Money::new(u64::MAX);
+        assert_eq!(max.add(Money::new(0)), Ok(max));
+        assert!(max.add(Money::new(1)).unwrap_err().contains("overflows"));
+        assert_eq!(Money::new(1).add(Money::new(2)), Ok(Money::new(3)));
+    }
+
+    #[test]
+    fn subtracting_more_than_the_balance_fails() {
+        let balance = Money::new(60);
+        assert_eq!(balance.sub(Money::new(60)), Ok(Money::new(0)));
+        assert_eq!(
+            balance.sub(Money::new(61)),
+            Err("61 exceeds the balance of 60".to_string())
+        );
+    }
+
+    #[test]
+    fn fee_and_net_always_sum_to_the_amount() {
+        let amounts = (0..1_000)
+            .chain((0..64).map(|shift| 1u64 << shift))
+            .chain([u64::MAX - 1, u64::MAX]);
+        for amount in amounts {
+            for bps in [0, 1, 25, 299, 5_000, 9_999, 10_000] {
+                let rate = BasisPoints::new(bps).unwrap();
+                let (fee, net) = Money::new(amount).split_fee(rate);
+                assert_eq!(
+                    fee.add(net),
+                    Ok(Money::new(amount)),
+                    "{} at {}",
+                    amount,
+                    bps
+                );
+                assert!(fee.get() as u128 * 10_000 <= amount as u128 * bps as u128);
+            }
+        }
+    }
+
+    #[test]
+    fn fee_rounds_down() {
+        let rate = BasisPoints::new(50).unwrap();
+        assert_eq!(
+            Money::new(199).split_fee(rate),
+            (Money::new(0), Money::new(199))
+        );
+        assert_eq!(
+            Money::new(200).split_fee(rate),
+            (Money::new(1), Money::new(199))
+        );
+        assert_eq!(BasisPoints::new(10_001), None);
+    }
+}
@@ -0,0 +1,123 @@
+// Copyright (c), Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::EnclaveError;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::str::FromStr;
+
+/// JSON key style for responses. Requests accept both styles regardless
+/// (camelCase via serde aliases). Signatures are over BCS or JCS of the
+/// snake_case structs, so only the JSON representation changes: camelCase
+/// clients must map keys back before reconstructing a JCS message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FieldNaming {
+    #[default]
+    SnakeCase,
+    CamelCase,
+}
+
+impl FieldNaming {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FieldNaming::SnakeCase => "snake_case",
+            FieldNaming::CamelCase => "camelCase",
+        }
+    }
+}
+
+impl FromStr for FieldNaming {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "snake_case" => Ok(FieldNaming::SnakeCase),
+            "camelCase" => Ok(FieldNaming::CamelCase),
+            other => Err(format!("unknown field naming {:?}", other)),
+        }
+    }
+}
+
+/// Serialize `value` to JSON with object keys in the requested style.
+pub fn render<T: Serialize>(value: &T, naming: FieldNaming) -> Result<Value, EnclaveError> {
+    let json = serde_json::to_value(value)?;
+    Ok(match naming {
+        FieldNaming::SnakeCase => json,
+        FieldNaming::CamelCase => camel_case_keys(json),
+    })
+}
+
+fn camel_case_keys(value: Value) -> Value {
+    match value {
+        Value::Object(map) => Value::Object(
+            map.into_iter()
+                .map(|(k, v)| (snake_to_camel(&k), camel_case_keys(v)))
+                .collect::<Map<String, Value>>(),
+        ),
+        Value::Array(items) => Value::Array(items.into_iter().map(camel_case_keys).collect()),
+        other => other,
+    }
+}
+
+fn snake_to_camel(key: &str) -> String {
+    let mut out = String::with_capacity(key.len());
+    let mut upper_next = false;
+    for c in key.chars() {
+        if c == '_' {
+            upper_next = true;
+        } else if upper_next {
+            out.extend(c.to_uppercase());
+            upper_next = false;
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::orders::order::OrderRequest;
+    use serde_json::json;
+
+    fn request() -> OrderRequest {
+        serde_json::from_value(json!({
+            "version": 1,
+            "order_id": "o1",
+            "customer": "alice",
+            "merchant": "acme",
+            "amount": 100,
+            "currency": "USD",
+            "action": "initiate",
+            "client_timestamp_ms": 1_700_000_000_000u64,
+            "client_reference": "INV-1",
+            "expires_at_ms": 1_700_000_060_000u64,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn both_naming_styles_round_trip() {
+        let snake = render(&request(), FieldNaming::SnakeCase).unwrap();
+        let camel = render(&request(), FieldNaming::CamelCase).unwrap();
+        assert_eq!(snake["order_id"], "o1");
+        assert_eq!(camel["orderId"], "o1");
+        assert_eq!(camel["clientTimestampMs"], 1_700_000_000_000u64);
+        assert!(camel.get("order_id").is_none());
+
+        for rendered in [snake.clone(), camel] {
+            let parsed: OrderRequest = serde_json::from_value(rendered).unwrap();
+            assert_eq!(render(&parsed, FieldNaming::SnakeCase).unwrap(), snake);
+        }
+    }
+
+    #[test]
+    fn naming_parses_from_its_name() {
+        for naming in [FieldNaming::SnakeCase, FieldNaming::CamelCase] {
+            assert_eq!(naming.as_str().parse(), Ok(naming));
+        }
+        assert!("kebab-case".parse::<FieldNaming>().is_err());
+        assert_eq!(snake_to_camel("server_timestamp_ms"), "serverTimestampMs");
+    }
+}
@@ -0,0 +1,308 @@
+// Copyright (c), Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Content negotiation for the order routes: request bodies may be JSON or
+//! BCS (`application/bcs`), and `/orders/process` answers in BCS when the
+//! client's `Accept` asks for it.
+
+use crate::EnclaveError;
+use axum::async_trait;
+use axum::body::Bytes;
+use axum::extract::{FromRequest, Request};
+use axum::http::header::{ACCEPT, CONTENT_TYPE};
+use axum::http::HeaderMap;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use base64::engine::general_purpose::STANDARD as B64;
+use base64::Engine;
+use fastcrypto::encoding::{Encoding, Hex};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use super::order::signing_message_for;
+use super::{OrderAction, OrderRequest, OrderV2Fields, SignedOrderResponse, SigningFormat};
+
+pub const BCS_CONTENT_TYPE: &str = "application/bcs";
+
+/// BCS wire form of `OrderRequest`. BCS is not self-describing, so every
+/// field is always present, in this order, and `metadata` travels as JSON
+/// text since BCS cannot carry an arbitrary JSON value.
+#[derive(Debug, Deserialize)]
+#[cfg_attr(test, derive(Serialize))]
+struct BcsOrderRequest {
+    version: u8,
+    order_id: String,
+    customer: String,
+    merchant: String,
+    amount: u64,
+    currency: String,
+    action: OrderAction,
+    client_timestamp_ms: Option<u64>,
+    metadata: Option<String>,
+    v2: Option<OrderV2Fields>,
+    client_reference: Option<String>,
+    allow_replay: bool,
+    expires_at_ms: Option<u64>,
+    settle_amount: Option<u64>,
+    dry_run: bool,
+}
+
+impl TryFrom<BcsOrderRequest> for OrderRequest {
+    type Error = String;
+
+    fn try_from(wire: BcsOrderRequest) -> Result<Self, Self::Error> {
+        let metadata = wire
+            .metadata
+            .map(|text| serde_json::from_str(&text))
+            .transpose()
+            .map_err(|e| format!("metadata is not valid JSON: {}", e))?;
+        Ok(OrderRequest {
+            version: wire.version,
+            order_id: wire.order_id,
+            customer: wire.customer,
+            merchant: wire.merchant,
+            amount: wire.amount,
+            currency: wire.currency,
+            action: wire.action,
+            client_timestamp_ms: wire.client_timestamp_ms,
+            metadata,
+            v2: wire.v2,
+            client_reference: wire.client_reference,
+            allow_replay: wire.allow_replay,
+            expires_at_ms: wire.expires_at_ms,
+            settle_amount: wire.settle_amount,
+            dry_run: wire.dry_run,
+        })
+    }
+}
+
+/// Request bodies that can also arrive as BCS.
+pub trait FromBcs: Sized {
+    fn from_bcs(bytes: &[u8]) -> Result<Self, String>;
+}
+
+impl FromBcs for OrderRequest {
+    fn from_bcs(bytes: &[u8]) -> Result<Self, String> {
+        bcs::from_bytes::<BcsOrderRequest>(bytes)
+            .map_err(|e| format!("invalid BCS order: {}", e))?
+            .try_into()
+    }
+}
+
+impl FromBcs for Vec<OrderRequest> {
+    fn from_bcs(bytes: &[u8]) -> Result<Self, String> {
+        bcs::from_bytes::<Vec<BcsOrderRequest>>(bytes)
+            .map_err(|e| format!("invalid BCS order batch: {}", e))?
+            .into_iter()
+            .map(OrderRequest::try_from)
+            .collect()
+    }
+}
+
+/// Extractor for an order body in either encoding, chosen by
+/// `Content-Type`; anything but JSON or BCS is refused with 415. Also
+/// records whether the client's `Accept` prefers a BCS response.
+pub struct OrderBody<T> {
+    pub body: T,
+    pub respond_bcs: bool,
+}
+
+#[async_trait]
+impl<S, T> FromRequest<S> for OrderBody<T>
+where
+    S: Send + Sync,
+    T: DeserializeOwned + FromBcs,
+{
+    type Rejection = Response;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let respond_bcs = accepts_bcs(req.headers());
+        let mime = req
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.split(';').next())
+            .map(|v| v.trim().to_ascii_lowercase())
+            .unwrap_or_default();
+
+        let body = if mime == BCS_CONTENT_TYPE {
+            let bytes = Bytes::from_request(req, state)
+                .await
+                .map_err(IntoResponse::into_response)?;
+            T::from_bcs(&bytes).map_err(|message| {
+                EnclaveError::InvalidRequest {
+                    code: "invalid_bcs",
+                    message,
+                }
+                .into_response()
+            })?
+        } else if mime == "application/json" || mime.ends_with("+json") {
+            let Json(body) = Json::<T>::from_request(req, state)
+                .await
+                .map_err(IntoResponse::into_response)?;
+            body
+        } else {
+            return Err(EnclaveError::UnsupportedMediaType(format!(
+                "expected application/json or {}, got {:?}",
+                BCS_CONTENT_TYPE, mime
+            ))
+            .into_response());
+        };
+        Ok(Self { body, respond_bcs })
+    }
+}
+
+fn accepts_bcs(headers: &HeaderMap) -> bool {
+    headers
+        .get_all(ACCEPT)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .any(|v| {
+            v.split(';')
+                .next()
+                .is_some_and(|mime| mime.trim().eq_ignore_ascii_case(BCS_CONTENT_TYPE))
+        })
+}
+
+/// BCS response body for `Accept: application/bcs`: the exact bytes the
+/// signature covers, with raw signature and key, so on-chain tooling can
+/// verify without touching JSON.
+#[derive(Debug, Serialize)]
+#[cfg_attr(test, derive(Deserialize))]
+struct BcsSignedOrder {
+    signing_message: Vec<u8>,
+    signature: Vec<u8>,
+    public_key: Vec<u8>,
+    signature_v2: Option<Vec<u8>>,
+    /// The digest `signature` covers, with hash-before-sign.
+    digest: Option<Vec<u8>>,
+}
+
+/// Encode `signed` as a BCS `application/bcs` response.
+pub fn bcs_response(
+    signed: &SignedOrderResponse,
+    format: SigningFormat,
+) -> Result<Response, EnclaveError> {
+    let decode = |b64: &str| {
+        B64.decode(b64)
+            .map_err(|e| EnclaveError::GenericError(format!("stored signature: {}", e)))
+    };
+    let body = BcsSignedOrder {
+        signing_message: signing_message_for(&signed.response, format)?,
+        signature: decode(&signed.signature)?,
+        public_key: decode(&signed.public_key)?,
+        signature_v2: signed.signature_v2.as_deref().map(decode).transpose()?,
+        digest: signed
+            .digest
+            .as_deref()
+            .map(|hex| {
+                Hex::decode(hex)
+                    .map_err(|e| EnclaveError::GenericError(format!("stored digest: {}", e)))
+            })
+            .transpose()?,
+    };
+    let bytes = bcs::to_bytes(&body)
+        .map_err(|e| EnclaveError::GenericError(format!("BCS encoding: {}", e)))?;
+    Ok(([(CONTENT_TYPE, BCS_CONTENT_TYPE)], bytes).into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::orders::crypto;
+    use crate::{build_router, AppState};
+    use axum::body::{to_bytes, Body};
+    use axum::http::{Request, StatusCode};
+    use serde_json::json;
+    use tower::ServiceExt;
+
+    fn fixed_seed(seed: &mut [u8; 32]) -> Result<(), &'static str> {
+        seed.fill(7);
+        Ok(())
+    }
+
+    fn wire_request() -> BcsOrderRequest {
+        BcsOrderRequest {
+            version: 1,
+            order_id: "o1".to_string(),
+            customer: "alice".to_string(),
+            merchant: "acme".to_string(),
+            amount: 100,
+            currency: "USD".to_string(),
+            action: OrderAction::Initiate,
+            client_timestamp_ms: None,
+            metadata: Some(r#"{"note":"hi"}"#.to_string()),
+            v2: None,
+            client_reference: Some("INV-1".to_string()),
+            allow_replay: false,
+            expires_at_ms: None,
+            settle_amount: None,
+            dry_run: false,
+        }
+    }
+
+    #[test]
+    fn bcs_request_decodes_like_its_json_twin() {
+        let from_bcs = OrderRequest::from_bcs(&bcs::to_bytes(&wire_request()).unwrap()).unwrap();
+        let from_json: OrderRequest = serde_json::from_value(json!({
+            "version": 1,
+            "order_id": "o1",
+            "customer": "alice",
+            "merchant": "acme",
+            "amount": 100,
+            "currency": "USD",
+            "action": "initiate",
+            "client_timestamp_ms": null,
+            "metadata": { "note": "hi" },
+            "client_reference": "INV-1",
+        }))
+        .unwrap();
+        assert_eq!(
+            serde_json::to_value(&from_bcs).unwrap(),
+            serde_json::to_value(&from_json).unwrap()
+        );
+        assert!(OrderRequest::from_bcs(b"\x01").is_err());
+    }
+
+    #[tokio::test]
+    async fn bcs_order_is_signed_over_the_same_message_as_json() {
+        crypto::ensure_initialized_with(crypto::SchemeKind::Ed25519, fixed_seed).unwrap();
+        let app = build_router(AppState::for_tests(Default::default()));
+        let response = app
+            .clone()
+            .oneshot(
+                Request::post("/orders/process")
+                    .header(CONTENT_TYPE, BCS_CONTENT_TYPE)
+                    .header(ACCEPT, BCS_CONTENT_TYPE)
+                    .body(Body::from(bcs::to_bytes(&wire_request()).unwrap()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[CONTENT_TYPE], BCS_CONTENT_TYPE);
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let bcs_signed: BcsSignedOrder = bcs::from_bytes(&body).unwrap();
+
+        // The same order as JSON, as stored and served by `GET /orders/:id`.
+        let response = app
+            .oneshot(Request::get("/orders/o1").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let json_signed: SignedOrderResponse = serde_json::from_slice(&body).unwrap();
+        assert_eq!(
+            json_signed.response.client_reference.as_deref(),
+            Some("INV-1")
+        );
+        assert_eq!(
+            bcs_signed.signing_message,
+            signing_message_for(&json_signed.response, SigningFormat::Bcs).unwrap()
+        );
+        assert_eq!(
+            bcs_signed.signature,
+            B64.decode(&json_signed.signature).unwrap()
+        );
+    }
+}
@@ -1,11 +1,15 @@
 // Copyright (c), Mysten Labs, Inc.
 // SPDX-License-Identifier: Apache-2.0
 
-use base64::engine::general_purpose::STANDARD as B64;
+use base64::engine::general_purpose::{STANDARD as B64, URL_SAFE_NO_PAD};
 use base64::Engine;
+use fastcrypto::encoding::{Encoding, Hex};
+use fastcrypto::hash::{Blake2b256, HashFunction};
 use serde::{Deserialize, Serialize};
 use tracing::info;
 
+use crate::common::clock::unix_time_ms;
+
 // ============================================
 // ✅ INTENT SCOPES (must match Move contract)
 // ============================================
@@ -13,6 +17,7 @@ const ORDER_INTENT_INITIATE: u8 = 0;
 const ORDER_INTENT_DEPOSIT: u8 = 1;
 const ORDER_INTENT_RELEASE: u8 = 2;
 const ORDER_INTENT_REFUND: u8 = 3;
+const ORDER_INTENT_CANCEL: u8 = 4;
 
 // V2 intent scopes — disjoint from V1 so a V1 signature can NEVER be
 // replayed as V2 even at the byte level. Used by the hardened signing
@@ -21,6 +26,7 @@ const ORDER_INTENT_V2_INITIATE: u8 = 0x10;
 const ORDER_INTENT_V2_DEPOSIT: u8 = 0x11;
 const ORDER_INTENT_V2_RELEASE: u8 = 0x12;
 const ORDER_INTENT_V2_REFUND: u8 = 0x13;
+const ORDER_INTENT_V2_CANCEL: u8 = 0x14;
 
 // ============================================
 // ✅ ACTION/STATUS CONSTANTS (for BCS serialization)
@@ -30,20 +36,137 @@ const ACTION_INITIATE: u8 = 0;
 const ACTION_DEPOSIT: u8 = 1;
 const ACTION_RELEASE: u8 = 2;
 const ACTION_REFUND: u8 = 3;
+const ACTION_CANCEL: u8 = 4;
 
 const STATUS_PENDING: u8 = 0;
 const STATUS_ESCROWED: u8 = 1;
 const STATUS_RELEASED: u8 = 2;
 const STATUS_REFUNDED: u8 = 3;
 const STATUS_REJECTED: u8 = 4;
+const STATUS_CANCELLED: u8 = 5;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+// ============================================
+// ✅ SIGNED LAYOUT VERSION
+// First byte of the V1 signed payload. Bump whenever the field layout of
+// `BcsSignableOrderResponse` changes so verifiers can dispatch on it,
+// independently of the user-facing protocol `version`. v3 appends `sequence`,
+// v4 `expires_at_ms`, v5 `remaining_amount`, v6 `request_digest`, v7
+// `fee_amount` and `net_amount`, v8 `metadata_digest`, v9 `settle_amount`.
+// Every bump is a breaking change for on-chain verifiers: a Move struct
+// built for an older layout decodes different bytes and rejects the
+// signature without saying why. This repo ships no Move counterpart, so
+// integrators must mirror `BcsSignableOrderResponse` field for field and
+// refuse any `field_version` they were not built for.
+// ============================================
+pub const SIGNING_FIELD_VERSION: u8 = 9;
+
+/// Prefix of the canonical-JSON signing message, so a JCS signature can never
+/// be confused with a signature over some other JSON document. The protocol
+/// version follows it; see `jcs_domain_tag`.
+pub const JCS_DOMAIN_TAG: &[u8] = b"nautilus/order/jcs/v5";
+
+/// Protocol versions this server understands.
+pub const SUPPORTED_VERSIONS: &[u8] = &[1];
+
+/// Domain tag for protocol `version`: `JCS_DOMAIN_TAG || "/p" || version`,
+/// so a signature made under one protocol version cannot be presented as
+/// one made under another.
+pub fn jcs_domain_tag(version: u8) -> Vec<u8> {
+    let mut tag = JCS_DOMAIN_TAG.to_vec();
+    tag.extend(format!("/p{}", version).into_bytes());
+    tag
+}
+
+/// Refuse with 400 `unsupported_version` a protocol `version` not in
+/// `SUPPORTED_VERSIONS`, rather than sign a response claiming a version the
+/// server never understood.
+pub fn check_version(version: u8) -> Result<(), EnclaveError> {
+    if SUPPORTED_VERSIONS.contains(&version) {
+        return Ok(());
+    }
+    Err(EnclaveError::InvalidRequest {
+        code: "unsupported_version",
+        message: format!(
+            "protocol version {} is not supported; supported versions: {:?}",
+            version, SUPPORTED_VERSIONS
+        ),
+    })
+}
+
+/// Largest integer a JavaScript number holds exactly, 2^53 - 1. Under JCS
+/// every signed integer must be at most this, or a JS verifier re-encodes
+/// it differently and the signature no longer checks.
+pub const MAX_SAFE_INTEGER: u64 = (1 << 53) - 1;
+
+/// Refuse with 400 `amount_exceeds_safe_integer` an `amount` or
+/// `settle_amount` over `MAX_SAFE_INTEGER` when signing with JCS. Every
+/// other signed amount is bounded by `amount`.
+pub fn check_safe_amounts(req: &OrderRequest, format: SigningFormat) -> Result<(), EnclaveError> {
+    if format != SigningFormat::Jcs {
+        return Ok(());
+    }
+    [
+        ("amount", Some(req.amount)),
+        ("settle_amount", req.settle_amount),
+    ]
+    .into_iter()
+    .try_for_each(|(field, value)| match value {
+        Some(value) if value > MAX_SAFE_INTEGER => Err(unsafe_integer(field, value)),
+        _ => Ok(()),
+    })
+}
+
+fn unsafe_integer(field: &str, value: u64) -> EnclaveError {
+    EnclaveError::InvalidRequest {
+        code: "amount_exceeds_safe_integer",
+        message: format!(
+            "{} is {}, above {} which JCS signatures can carry",
+            field, value, MAX_SAFE_INTEGER
+        ),
+    }
+}
+
+/// Byte encoding the V1 signature is computed over.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SigningFormat {
+    /// `BCS(IntentMessage)`, verifiable on-chain by Move.
+    #[default]
+    Bcs,
+    /// `jcs_domain_tag(version) || canonical_json(response)` (RFC 8785), for
+    /// browser/Node verifiers without a BCS implementation.
+    Jcs,
+}
+
+impl SigningFormat {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SigningFormat::Bcs => "bcs",
+            SigningFormat::Jcs => "jcs",
+        }
+    }
+}
+
+impl std::str::FromStr for SigningFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "bcs" => Ok(SigningFormat::Bcs),
+            "jcs" | "canonical_json" => Ok(SigningFormat::Jcs),
+            other => Err(format!("unknown signing format {:?}", other)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub enum OrderAction {
     Initiate,
     Deposit,
     Release,
     Refund,
+    /// Withdraw a `Pending` order that was never funded.
+    Cancel,
 }
 
 impl OrderAction {
@@ -53,6 +176,17 @@ impl OrderAction {
             OrderAction::Deposit => ACTION_DEPOSIT,
             OrderAction::Release => ACTION_RELEASE,
             OrderAction::Refund => ACTION_REFUND,
+            OrderAction::Cancel => ACTION_CANCEL,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OrderAction::Initiate => "initiate",
+            OrderAction::Deposit => "deposit",
+            OrderAction::Release => "release",
+            OrderAction::Refund => "refund",
+            OrderAction::Cancel => "cancel",
         }
     }
 
@@ -62,6 +196,7 @@ impl OrderAction {
             OrderAction::Deposit => ORDER_INTENT_DEPOSIT,
             OrderAction::Release => ORDER_INTENT_RELEASE,
             OrderAction::Refund => ORDER_INTENT_REFUND,
+            OrderAction::Cancel => ORDER_INTENT_CANCEL,
         }
     }
 
@@ -74,11 +209,12 @@ impl OrderAction {
             OrderAction::Deposit => ORDER_INTENT_V2_DEPOSIT,
             OrderAction::Release => ORDER_INTENT_V2_RELEASE,
             OrderAction::Refund => ORDER_INTENT_V2_REFUND,
+            OrderAction::Cancel => ORDER_INTENT_V2_CANCEL,
         }
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub enum OrderStatus {
     Pending,
@@ -86,9 +222,21 @@ pub enum OrderStatus {
     Released,
     Refunded,
     Rejected,
+    Cancelled,
 }
 
 impl OrderStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OrderStatus::Pending => "pending",
+            OrderStatus::Escrowed => "escrowed",
+            OrderStatus::Released => "released",
+            OrderStatus::Refunded => "refunded",
+            OrderStatus::Rejected => "rejected",
+            OrderStatus::Cancelled => "cancelled",
+        }
+    }
+
     fn to_u8(&self) -> u8 {
         match self {
             OrderStatus::Pending => STATUS_PENDING,
@@ -96,6 +244,7 @@ impl OrderStatus {
             OrderStatus::Released => STATUS_RELEASED,
             OrderStatus::Refunded => STATUS_REFUNDED,
             OrderStatus::Rejected => STATUS_REJECTED,
+            OrderStatus::Cancelled => STATUS_CANCELLED,
         }
     }
 }
@@ -103,23 +252,115 @@ impl OrderStatus {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct OrderRequest {
     pub version: u8, // protocol version, start with 1
+    /// May be omitted on `Initiate` when server-side id allocation is on.
+    #[serde(default, alias = "orderId")]
     pub order_id: String,
     pub customer: String,
     pub merchant: String,
     pub amount: u64,         // minor units (e.g., cents)
     pub currency: String,    // e.g., "USD"
     pub action: OrderAction, // desired action
+    #[serde(alias = "clientTimestampMs")]
     pub client_timestamp_ms: Option<u64>,
     pub metadata: Option<serde_json::Value>,
     /// Optional V2 hardening fields. When present, the enclave additionally
     /// produces a V2 signature alongside V1 (shadow mode).
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub v2: Option<OrderV2Fields>,
+    /// Merchant's own opaque reference, echoed into the signed response.
+    #[serde(
+        default,
+        alias = "clientReference",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub client_reference: Option<String>,
+    /// Sign a `Release`/`Refund` even if this order already had one signed.
+    #[serde(default, alias = "allowReplay")]
+    pub allow_replay: bool,
+    /// Server time after which the order can no longer be deposited into,
+    /// released or refunded. Only read on `Initiate`; later actions use the
+    /// expiry recorded then. `None` never expires.
+    #[serde(
+        default,
+        alias = "expiresAtMs",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub expires_at_ms: Option<u64>,
+    /// Part of the escrowed balance a `Release`/`Refund` moves. `None`
+    /// settles the whole remaining balance.
+    #[serde(
+        default,
+        alias = "settleAmount",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub settle_amount: Option<u64>,
+    /// Validate and sign as usual but change no state: nothing is stored,
+    /// audited or sent to the webhook, and the response is marked as a
+    /// simulation in `notes`.
+    #[serde(default, alias = "dryRun")]
+    pub dry_run: bool,
+}
+
+/// Longest `order_id` accepted.
+pub const MAX_ORDER_ID_LEN: usize = 128;
+
+/// Every problem found in an `OrderRequest`, one `"field: reason"` entry
+/// each, so a client can fix them all in one round trip.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationError {
+    pub fields: Vec<String>,
+}
+
+impl From<ValidationError> for EnclaveError {
+    fn from(err: ValidationError) -> Self {
+        EnclaveError::ValidationFailed(err.fields)
+    }
+}
+
+impl OrderRequest {
+    /// Basic shape checks run before anything is signed. The protocol
+    /// version is checked separately by `check_version`.
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        let mut fields = Vec::new();
+        if self.order_id.is_empty() {
+            fields.push("order_id: must not be empty".to_string());
+        } else if self.order_id.chars().count() > MAX_ORDER_ID_LEN {
+            fields.push(format!(
+                "order_id: must be at most {} characters",
+                MAX_ORDER_ID_LEN
+            ));
+        }
+        if self.customer.is_empty() {
+            fields.push("customer: must not be empty".to_string());
+        }
+        if self.merchant.is_empty() {
+            fields.push("merchant: must not be empty".to_string());
+        }
+        if self.amount == 0 {
+            fields.push("amount: must be greater than zero".to_string());
+        }
+        if self.currency.len() != 3 || !self.currency.bytes().all(|b| b.is_ascii_uppercase()) {
+            fields.push("currency: must be a 3-letter uppercase code".to_string());
+        }
+        match self.settle_amount {
+            Some(_) if !matches!(self.action, OrderAction::Release | OrderAction::Refund) => {
+                fields.push("settle_amount: only allowed on Release or Refund".to_string());
+            }
+            Some(0) => fields.push("settle_amount: must be greater than zero".to_string()),
+            _ => {}
+        }
+        if fields.is_empty() {
+            Ok(())
+        } else {
+            Err(ValidationError { fields })
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SignableOrderResponse {
-    pub version: u8, // protocol version
+    pub field_version: u8, // signed layout version, see SIGNING_FIELD_VERSION
+    pub version: u8,       // protocol version
     pub order_id: String,
     pub action: OrderAction,
     pub status: OrderStatus,
@@ -128,12 +369,45 @@ pub struct SignableOrderResponse {
     pub server_timestamp_ms: u64,
     pub escrow_tx_id: Option<String>, // on-chain tx id or reference, if any
     pub notes: Option<String>,        // reason for rejection or info
+    pub client_reference: Option<String>, // echoed from the request, opaque
+    /// Strictly increasing per signed response within one enclave lifetime;
+    /// restarts from 1 when the enclave restarts. 0 on a dry run.
+    #[serde(default)]
+    pub sequence: u64,
+    /// Expiry recorded when the order was initiated, if any.
+    #[serde(default)]
+    pub expires_at_ms: Option<u64>,
+    /// Escrowed balance left after this action; `None` before a deposit
+    /// and on rejections.
+    #[serde(default)]
+    pub remaining_amount: Option<u64>,
+    /// Hex `request_digest` of the request that produced this response.
+    #[serde(default)]
+    pub request_digest: String,
+    /// Escrow fee on `amount`, rounded down; see `apply_fee`.
+    #[serde(default)]
+    pub fee_amount: u64,
+    /// `amount - fee_amount`.
+    #[serde(default)]
+    pub net_amount: u64,
+    /// Hex `metadata_digest` of the request's `metadata`; `None` when it
+    /// had none. `request_digest` already covers metadata, but this lets a
+    /// holder of the metadata alone check it against the signature.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata_digest: Option<String>,
+    /// What this `Release`/`Refund` moved out of escrow, so each installment
+    /// is identified by its signature; `None` for other actions and on
+    /// rejections.
+    #[serde(default)]
+    pub settle_amount: Option<u64>,
 }
 
-/// BCS-serializable struct that matches the Move SignableOrderResponse exactly
-/// This is what gets wrapped in IntentMessage for signing
+/// BCS layout of the V1 signed payload at `SIGNING_FIELD_VERSION`, wrapped
+/// in IntentMessage for signing. A Move verifier must declare a struct with
+/// these fields in this order; appending one here bumps the version.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 struct BcsSignableOrderResponse {
+    field_version: u8,
     version: u8,
     order_id: Vec<u8>,
     action: u8,
@@ -143,11 +417,21 @@ struct BcsSignableOrderResponse {
     server_timestamp_ms: u64,
     escrow_tx_id: Option<Vec<u8>>,
     notes: Option<Vec<u8>>,
+    client_reference: Option<Vec<u8>>,
+    sequence: u64,
+    expires_at_ms: Option<u64>,
+    remaining_amount: Option<u64>,
+    request_digest: Vec<u8>,
+    fee_amount: u64,
+    net_amount: u64,
+    metadata_digest: Option<Vec<u8>>,
+    settle_amount: Option<u64>,
 }
 
 impl From<&SignableOrderResponse> for BcsSignableOrderResponse {
     fn from(resp: &SignableOrderResponse) -> Self {
         BcsSignableOrderResponse {
+            field_version: resp.field_version,
             version: resp.version,
             order_id: resp.order_id.as_bytes().to_vec(),
             action: resp.action.to_u8(),
@@ -157,12 +441,25 @@ impl From<&SignableOrderResponse> for BcsSignableOrderResponse {
             server_timestamp_ms: resp.server_timestamp_ms,
             escrow_tx_id: resp.escrow_tx_id.as_ref().map(|s| s.as_bytes().to_vec()),
             notes: resp.notes.as_ref().map(|s| s.as_bytes().to_vec()),
+            client_reference: resp
+                .client_reference
+                .as_ref()
+                .map(|s| s.as_bytes().to_vec()),
+            sequence: resp.sequence,
+            expires_at_ms: resp.expires_at_ms,
+            remaining_amount: resp.remaining_amount,
+            request_digest: resp.request_digest.as_bytes().to_vec(),
+            fee_amount: resp.fee_amount,
+            net_amount: resp.net_amount,
+            metadata_digest: resp.metadata_digest.as_ref().map(|s| s.as_bytes().to_vec()),
+            settle_amount: resp.settle_amount,
         }
     }
 }
 
 /// IntentMessage wrapper - matches Move's IntentMessage<P> struct exactly
-/// BCS serialization: intent (u8) + timestamp_ms (u64) + payload (BcsSignableOrderResponse)
+/// BCS serialization: intent (u8) + timestamp_ms (u64) + payload (BcsSignableOrderResponse),
+/// where the payload opens with its `field_version` byte
 #[derive(Debug, Clone, Serialize, Deserialize)]
 struct IntentMessage {
     intent: u8,
@@ -173,10 +470,27 @@ struct IntentMessage {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SignedOrderResponse {
     pub response: SignableOrderResponse,
-    pub signature: String,  // base64(ed25519 signature over BCS(IntentMessage))
-    pub public_key: String, // base64(ed25519 public key), also emitted via health
-    pub scheme: String,     // "ed25519"
-    /// Base64 ed25519 signature over BCS(IntentMessageV2). Present only
+    pub signature: String,  // base64(`scheme` signature over the `alg` message)
+    pub public_key: String, // base64(`scheme` public key), also emitted via health
+    pub scheme: String,     // "ed25519" or "secp256k1"
+    /// Signing message format the V1 signature covers: "bcs" or "jcs".
+    #[serde(default = "default_alg")]
+    pub alg: String,
+    /// Hex of the first 8 bytes of SHA-256(public key), for cheap key
+    /// pinning. Derivable from `public_key`, so not signed.
+    #[serde(default)]
+    pub public_key_fingerprint: String,
+    /// Id of the signing key, as in the JWK `kid`. `/orders/verify` checks
+    /// the signature against the key it names, which may have been retired
+    /// by a rotation since.
+    #[serde(default)]
+    pub kid: String,
+    /// Hex Blake2b-256 of the V1 signing message. Present only with
+    /// hash-before-sign, in which case `signature` covers these 32 bytes
+    /// rather than the message.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub digest: Option<String>,
+    /// Base64 `scheme` signature over BCS(IntentMessageV2). Present only
     /// when the request supplied `v2` fields. Same enclave master key as V1.
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub signature_v2: Option<String>,
@@ -185,82 +499,468 @@ pub struct SignedOrderResponse {
     /// of the verification check.
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub v2: Option<OrderV2Fields>,
+    /// Base64 tagged COSE_Sign1 over the same message as `signature`.
+    /// Present only when requested with `?format=cose`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cose_sign1: Option<String>,
+    /// Base64 ed25519 signature by the operator key over the same message
+    /// as `signature`. Present only when operator co-signing is configured.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub operator_signature: Option<String>,
 }
 
 // Import crypto from the same orders module
-use super::crypto;
+use super::crypto::{self, KeyScope};
+use super::money::{BasisPoints, Money};
+use crate::EnclaveError;
+
+/// A response or request that could not be encoded. Every field is plain
+/// data, so this is a bug rather than bad input, and a 500.
+fn encoding_failed(encoding: &str, e: impl std::fmt::Display) -> EnclaveError {
+    EnclaveError::GenericError(format!("{} encoding failed: {}", encoding, e))
+}
 
 /// Creates the signing message that matches Move's verify_signature expectation
 /// Format: BCS(IntentMessage { intent, timestamp_ms, payload })
-fn signing_message(resp: &SignableOrderResponse) -> Vec<u8> {
+pub fn signing_message(resp: &SignableOrderResponse) -> Result<Vec<u8>, EnclaveError> {
     let bcs_payload = BcsSignableOrderResponse::from(resp);
     let intent_msg = IntentMessage {
         intent: resp.action.to_intent(),
         timestamp_ms: resp.server_timestamp_ms,
         payload: bcs_payload,
     };
-    bcs::to_bytes(&intent_msg).expect("BCS serialization should not fail for canonical structs")
+    bcs::to_bytes(&intent_msg).map_err(|e| encoding_failed("BCS", e))
+}
+
+/// Canonical-JSON signing message: `jcs_domain_tag(version) || JCS(response)`.
+fn canonical_json_message(resp: &SignableOrderResponse) -> Result<Vec<u8>, EnclaveError> {
+    // serde_json is built without `preserve_order`, so objects round-tripped
+    // through `Value` come out with sorted keys, and `to_vec` is compact.
+    // Every number in the response is an integer, which JCS renders
+    // identically only up to MAX_SAFE_INTEGER; `check_safe_amounts` refuses
+    // larger ones up front and this catches any that slip through.
+    let value = serde_json::to_value(resp).map_err(|e| encoding_failed("JSON", e))?;
+    if let serde_json::Value::Object(fields) = &value {
+        if let Some((field, n)) = fields.iter().find_map(|(field, v)| {
+            v.as_u64()
+                .filter(|n| *n > MAX_SAFE_INTEGER)
+                .map(|n| (field, n))
+        }) {
+            return Err(unsafe_integer(field, n));
+        }
+    }
+    let mut msg = jcs_domain_tag(resp.version);
+    msg.extend(serde_json::to_vec(&value).map_err(|e| encoding_failed("JSON", e))?);
+    Ok(msg)
+}
+
+/// The exact bytes the V1 signature covers under `format`.
+pub fn signing_message_for(
+    resp: &SignableOrderResponse,
+    format: SigningFormat,
+) -> Result<Vec<u8>, EnclaveError> {
+    match format {
+        SigningFormat::Bcs => signing_message(resp),
+        SigningFormat::Jcs => canonical_json_message(resp),
+    }
+}
+
+/// Hex Blake2b-256 of the canonical JSON of `req` (sorted keys, compact,
+/// absent optional fields omitted), as signed into the response so a
+/// dispute can be settled by re-hashing the original request. Covers the
+/// request as processed, i.e. with any server-allocated `order_id`.
+pub fn request_digest(req: &OrderRequest) -> Result<String, EnclaveError> {
+    // As in `canonical_json_message`, round-tripping through `Value` sorts
+    // the keys.
+    let value = serde_json::to_value(req).map_err(|e| encoding_failed("JSON", e))?;
+    let bytes = serde_json::to_vec(&value).map_err(|e| encoding_failed("JSON", e))?;
+    Ok(Hex::encode(Blake2b256::digest(&bytes).digest))
+}
+
+/// Hex Blake2b-256 of the compact, key-sorted JSON of `metadata`, or `None`
+/// without metadata. Hashed the same way `request_digest` hashes requests.
+pub fn metadata_digest(
+    metadata: Option<&serde_json::Value>,
+) -> Result<Option<String>, EnclaveError> {
+    let Some(value) = metadata else {
+        return Ok(None);
+    };
+    let bytes = serde_json::to_vec(value).map_err(|e| encoding_failed("JSON", e))?;
+    Ok(Some(Hex::encode(Blake2b256::digest(&bytes).digest)))
+}
+
+/// Blake2b-256 of a signing message, as Sui hashes messages before signing.
+/// With hash-before-sign the V1 signature covers this digest instead of the
+/// message itself.
+pub fn message_digest(message: &[u8]) -> [u8; 32] {
+    Blake2b256::digest(message).digest
+}
+
+/// Recompute the V1 signing message of a signed response, using the format
+/// named by its `alg`, and check `signature` against the key named by
+/// `kid`, current or retired. Responses without a `kid` are checked
+/// against their own `public_key`, but only if it is a key this enclave
+/// holds, so a response signed with any other key is `untrusted_key` even
+/// if its signature is sound. A response carrying a `digest` must match
+/// the digest of the recomputed message, and is checked over it.
+pub fn verify_signed_response(signed: &SignedOrderResponse) -> Result<bool, &'static str> {
+    let format: SigningFormat = signed.alg.parse().map_err(|_| "unsupported_alg")?;
+    let signature: [u8; 64] = B64
+        .decode(signed.signature.trim())
+        .map_err(|_| "signature_not_base64")?
+        .try_into()
+        .map_err(|_| "signature_wrong_length")?;
+    let mut message =
+        signing_message_for(&signed.response, format).map_err(|_| "response_not_encodable")?;
+    if let Some(claimed) = &signed.digest {
+        let digest = message_digest(&message);
+        if !Hex::encode(digest).eq_ignore_ascii_case(claimed.trim()) {
+            return Ok(false);
+        }
+        message = digest.to_vec();
+    }
+    let public_key = if signed.kid.is_empty() {
+        let kid = crypto::kid_for_public_key(&signed.public_key)?;
+        crypto::public_key_for_kid(&kid).ok_or("untrusted_key")?
+    } else {
+        crypto::public_key_for_kid(&signed.kid).ok_or("unknown_kid")?
+    };
+    crypto::verify(&message, &signature, &public_key)
+}
+
+/// Base64 COSE_Sign1 envelope whose payload is the V1 signing message.
+pub fn cose_sign1(
+    resp: &SignableOrderResponse,
+    format: SigningFormat,
+    scope: KeyScope,
+) -> Result<String, EnclaveError> {
+    let envelope =
+        super::cose::sign1(&signing_message_for(resp, format)?, scope).map_err(key_unavailable)?;
+    Ok(B64.encode(envelope))
+}
+
+/// Refuse to sign a response whose signing message is longer than `max`
+/// bytes, so signed artifacts stay within what downstream verifiers accept.
+pub fn check_signing_message_size(
+    resp: &SignableOrderResponse,
+    format: SigningFormat,
+    max: usize,
+) -> Result<(), EnclaveError> {
+    let len = signing_message_for(resp, format)?.len();
+    if len > max {
+        return Err(EnclaveError::InvalidRequest {
+            code: "signing_message_too_large",
+            message: format!("signing message would be {} bytes, limit is {}", len, max),
+        });
+    }
+    Ok(())
+}
+
+/// Allocate a collision-resistant order id: base64url of 16 random bytes.
+pub fn allocate_order_id() -> Result<String, &'static str> {
+    let mut bytes = [0u8; 16];
+    getrandom::getrandom(&mut bytes).map_err(|_| "rng_unavailable")?;
+    Ok(URL_SAFE_NO_PAD.encode(bytes))
+}
+
+/// Escrow state machine: the status `action` moves an order to from
+/// `prior` (`None` for an order not seen before), or why it may not.
+///
+/// Initiate: none -> Pending; Deposit: Pending -> Escrowed;
+/// Release: Escrowed -> Released; Refund: Escrowed -> Refunded;
+/// Cancel: Pending -> Cancelled.
+pub fn next_status(prior: Option<OrderStatus>, action: OrderAction) -> Result<OrderStatus, String> {
+    match (prior, action) {
+        (None, OrderAction::Initiate) => Ok(OrderStatus::Pending),
+        (Some(OrderStatus::Pending), OrderAction::Deposit) => Ok(OrderStatus::Escrowed),
+        (Some(OrderStatus::Escrowed), OrderAction::Release) => Ok(OrderStatus::Released),
+        (Some(OrderStatus::Escrowed), OrderAction::Refund) => Ok(OrderStatus::Refunded),
+        (Some(OrderStatus::Pending), OrderAction::Cancel) => Ok(OrderStatus::Cancelled),
+        (Some(OrderStatus::Escrowed), OrderAction::Cancel) => {
+            Err("Cancel is not allowed once funds are escrowed; use Refund".to_string())
+        }
+        (None, action) => Err(format!("{:?} on an order that was never initiated", action)),
+        (Some(status), action) => Err(format!("{:?} is not allowed from {:?}", action, status)),
+    }
+}
+
+/// Why `client_ts` is more than `max_skew_ms` away from `server_ts`, in
+/// either direction. A missing client timestamp, or 0, is not checked.
+pub fn check_clock_skew(
+    client_ts: Option<u64>,
+    server_ts: u64,
+    max_skew_ms: u64,
+) -> Result<(), String> {
+    let Some(client_ts) = client_ts.filter(|&ts| ts != 0) else {
+        return Ok(());
+    };
+    let skew = client_ts.abs_diff(server_ts);
+    if skew > max_skew_ms {
+        let direction = if client_ts > server_ts {
+            "ahead of"
+        } else {
+            "behind"
+        };
+        return Err(format!(
+            "client clock is {} ms {} server time, limit is {} ms",
+            skew, direction, max_skew_ms
+        ));
+    }
+    Ok(())
+}
+
+/// The expiry that governs `req`: its own `expires_at_ms` on `Initiate`,
+/// otherwise the one `recorded` when the order was initiated.
+pub fn effective_expiry(req: &OrderRequest, recorded: Option<u64>) -> Option<u64> {
+    match req.action {
+        OrderAction::Initiate => req.expires_at_ms,
+        _ => recorded,
+    }
+}
+
+/// Why `action` may not be taken at `server_ts` on an order expiring at
+/// `expires_at_ms`. `Cancel` stays allowed so an expired order can still be
+/// withdrawn, and `Initiate` is refused an expiry that has already passed.
+pub fn check_expiry(
+    action: OrderAction,
+    expires_at_ms: Option<u64>,
+    server_ts: u64,
+) -> Result<(), String> {
+    let Some(expires_at_ms) = expires_at_ms else {
+        return Ok(());
+    };
+    if server_ts <= expires_at_ms || action == OrderAction::Cancel {
+        return Ok(());
+    }
+    Err(match action {
+        OrderAction::Initiate => format!("expires_at_ms {} is already in the past", expires_at_ms),
+        _ => format!("order expired at {} ms", expires_at_ms),
+    })
 }
 
-pub fn make_response(req: &OrderRequest) -> SignableOrderResponse {
+/// Outcome of `settle`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Settlement {
+    pub status: OrderStatus,
+    /// Escrowed balance after the action.
+    pub remaining: Option<u64>,
+    /// What a `Release`/`Refund` moved out of escrow; `None` otherwise.
+    pub settled: Option<u64>,
+}
+
+/// Status and escrowed balance after moving `req` to `status`, given the
+/// balance `escrowed` before it. A `Deposit` escrows the full `amount`; a
+/// `Release`/`Refund` of less than the balance keeps the order `Escrowed`,
+/// and one of more than the balance is refused.
+pub fn settle(
+    req: &OrderRequest,
+    status: OrderStatus,
+    escrowed: Option<u64>,
+) -> Result<Settlement, String> {
+    match status {
+        OrderStatus::Escrowed => Ok(Settlement {
+            status,
+            remaining: Some(req.amount),
+            settled: None,
+        }),
+        OrderStatus::Released | OrderStatus::Refunded => {
+            let balance = Money::new(escrowed.unwrap_or(req.amount));
+            let settled = req.settle_amount.map_or(balance, Money::new);
+            let remaining = balance
+                .sub(settled)
+                .map_err(|reason| format!("settle_amount: {}", reason))?;
+            Ok(Settlement {
+                status: if remaining.is_zero() {
+                    status
+                } else {
+                    OrderStatus::Escrowed
+                },
+                remaining: Some(remaining.get()),
+                settled: Some(settled.get()),
+            })
+        }
+        _ => Ok(Settlement {
+            status,
+            remaining: escrowed,
+            settled: None,
+        }),
+    }
+}
+
+/// Response for `req` given the order's `prior` status, its governing
+/// `expires_at_ms` (see `effective_expiry`) and its `escrowed` balance. A
+/// client clock too far off, an expired order, an illegal transition or an
+/// over-settlement still yields a response, with status `Rejected` and the
+/// reason in `notes`, so clients get a verifiable rejection.
+pub fn process_order(
+    req: &OrderRequest,
+    prior: Option<OrderStatus>,
+    expires_at_ms: Option<u64>,
+    escrowed: Option<u64>,
+    max_clock_skew_ms: u64,
+) -> Result<SignableOrderResponse, EnclaveError> {
     let server_ts = unix_time_ms();
+    let outcome = check_clock_skew(req.client_timestamp_ms, server_ts, max_clock_skew_ms)
+        .and_then(|()| check_expiry(req.action, expires_at_ms, server_ts))
+        .and_then(|()| next_status(prior, req.action))
+        .and_then(|status| settle(req, status, escrowed));
+    let mut resp = match outcome {
+        Ok(settlement) => {
+            let mut resp = build_response(req, settlement.status, None, server_ts)?;
+            resp.remaining_amount = settlement.remaining;
+            resp.settle_amount = settlement.settled;
+            resp
+        }
+        Err(reason) => {
+            info!(order_id = %req.order_id, %reason, "Rejecting order");
+            build_response(req, OrderStatus::Rejected, Some(reason), server_ts)?
+        }
+    };
+    resp.expires_at_ms = expires_at_ms;
+    Ok(resp)
+}
+
+/// Stateless response: maps the action straight to its target status
+/// without consulting the order's history.
+pub fn make_response(req: &OrderRequest) -> Result<SignableOrderResponse, EnclaveError> {
+    let status = match req.action {
+        OrderAction::Initiate => OrderStatus::Pending,
+        OrderAction::Deposit => OrderStatus::Escrowed,
+        OrderAction::Release => OrderStatus::Released,
+        OrderAction::Refund => OrderStatus::Refunded,
+        OrderAction::Cancel => OrderStatus::Cancelled,
+    };
+    build_response(req, status, None, unix_time_ms())
+}
+
+fn build_response(
+    req: &OrderRequest,
+    status: OrderStatus,
+    notes: Option<String>,
+    server_ts: u64,
+) -> Result<SignableOrderResponse, EnclaveError> {
     info!(
         "Processing order {} with action {:?}",
         req.order_id, req.action
     );
-
-    // Minimal, conservative mapping for now. We’ll enrich with real escrow logic later.
-    let (status, notes) = match req.action {
-        OrderAction::Initiate => (OrderStatus::Pending, None),
-        OrderAction::Deposit => (OrderStatus::Escrowed, None),
-        OrderAction::Release => (OrderStatus::Released, None),
-        OrderAction::Refund => (OrderStatus::Refunded, None),
-    };
-
     info!("Order {} status: {:?}", req.order_id, status);
 
-    SignableOrderResponse {
+    Ok(SignableOrderResponse {
+        field_version: SIGNING_FIELD_VERSION,
         version: req.version,
         order_id: req.order_id.clone(),
-        action: req.action.clone(),
+        action: req.action,
         status,
         amount: req.amount,
         currency: req.currency.clone(),
         server_timestamp_ms: server_ts,
         escrow_tx_id: None,
         notes,
-    }
+        client_reference: req.client_reference.clone(),
+        // Assigned just before signing; see `AppState::sequence`.
+        sequence: 0,
+        expires_at_ms: req.expires_at_ms,
+        remaining_amount: None,
+        request_digest: request_digest(req)?,
+        // No fee until `apply_fee` sets the configured rate.
+        fee_amount: 0,
+        net_amount: req.amount,
+        metadata_digest: metadata_digest(req.metadata.as_ref())?,
+        // Set by `process_order` for a settlement.
+        settle_amount: None,
+    })
+}
+
+/// Fill in `fee_amount` and `net_amount` for `amount` at `rate`.
+pub fn apply_fee(resp: &mut SignableOrderResponse, rate: BasisPoints) {
+    let (fee, net) = Money::new(resp.amount).split_fee(rate);
+    resp.fee_amount = fee.get();
+    resp.net_amount = net.get();
+}
+
+/// Maps a key-operation failure (no key installed yet) to a 503, so an early
+/// request is refused rather than crashing the worker.
+pub fn key_unavailable(reason: &'static str) -> EnclaveError {
+    EnclaveError::KeyUnavailable(reason)
+}
+
+pub fn sign_response(resp: &SignableOrderResponse) -> Result<SignedOrderResponse, EnclaveError> {
+    sign_response_as(resp, SigningFormat::Bcs)
+}
+
+/// Sign `resp` over the signing message for `format`.
+pub fn sign_response_as(
+    resp: &SignableOrderResponse,
+    format: SigningFormat,
+) -> Result<SignedOrderResponse, EnclaveError> {
+    sign_response_in(resp, format, KeyScope::Enclave)
+}
+
+/// Sign `resp` over the signing message for `format` with the `scope` key.
+pub fn sign_response_in(
+    resp: &SignableOrderResponse,
+    format: SigningFormat,
+    scope: KeyScope,
+) -> Result<SignedOrderResponse, EnclaveError> {
+    sign_response_hashed(resp, format, scope, false)
 }
 
-pub fn sign_response(resp: &SignableOrderResponse) -> SignedOrderResponse {
-    let msg = signing_message(resp);
+/// Like `sign_response_in`; with `hash_before_sign` the signature covers
+/// `message_digest` of the signing message, returned in `digest`.
+pub fn sign_response_hashed(
+    resp: &SignableOrderResponse,
+    format: SigningFormat,
+    scope: KeyScope,
+    hash_before_sign: bool,
+) -> Result<SignedOrderResponse, EnclaveError> {
+    let msg = signing_message_for(resp, format)?;
     info!(
-        "Signing message of {} bytes for order {}",
+        "Signing {} message of {} bytes for order {}",
+        format.as_str(),
         msg.len(),
         resp.order_id
     );
-    let sig = crypto::sign(&msg);
-    let pk_b64 = crypto::public_key_base64();
-    SignedOrderResponse {
+    let digest = hash_before_sign.then(|| message_digest(&msg));
+    let signed = match &digest {
+        Some(digest) => crypto::sign_with_key_in(scope, digest),
+        None => crypto::sign_with_key_in(scope, &msg),
+    }
+    .map_err(key_unavailable)?;
+    Ok(SignedOrderResponse {
         response: resp.clone(),
-        signature: B64.encode(sig),
-        public_key: pk_b64,
-        scheme: "ed25519".to_string(),
+        signature: B64.encode(signed.signature),
+        public_key: signed.public_key,
+        scheme: signed.scheme.as_str().to_string(),
+        alg: format.as_str().to_string(),
+        public_key_fingerprint: signed.kid.clone(),
+        kid: signed.kid,
+        digest: digest.map(Hex::encode),
         signature_v2: None,
         v2: None,
-    }
+        cose_sign1: None,
+        operator_signature: None,
+    })
+}
+
+fn default_alg() -> String {
+    SigningFormat::Bcs.as_str().to_string()
 }
 
 /// Sign V1 and (when V2 inputs are supplied) V2 in a single pass. Used by
 /// the HTTP handler so a request that opts into V2 receives both signatures
 /// in one response — the backend can then verify both independently.
+/// `format` and `hash_before_sign` apply to V1 only; V2 is always BCS. Both
+/// use the `scope` key.
 pub fn sign_response_with_v2(
     resp: &SignableOrderResponse,
     v2: Option<&OrderV2Fields>,
-) -> SignedOrderResponse {
-    let mut signed = sign_response(resp);
+    format: SigningFormat,
+    scope: KeyScope,
+    hash_before_sign: bool,
+) -> Result<SignedOrderResponse, EnclaveError> {
+    let mut signed = sign_response_hashed(resp, format, scope, hash_before_sign)?;
     if let Some(v2_fields) = v2 {
-        match sign_v2(resp, v2_fields) {
+        match sign_v2(resp, v2_fields, scope) {
             Ok(sig_v2) => {
                 signed.signature_v2 = Some(sig_v2);
                 signed.v2 = Some(v2_fields.clone());
@@ -273,7 +973,7 @@ pub fn sign_response_with_v2(
             }
         }
     }
-    signed
+    Ok(signed)
 }
 
 // ============================================================
@@ -300,12 +1000,14 @@ pub fn sign_response_with_v2(
 pub struct OrderV2Fields {
     /// Sui ObjectID of the OrderEscrow<T> as 64-char hex (with or without
     /// `0x` prefix) OR a 32-byte raw array (after JSON normalization).
+    #[serde(alias = "escrowId")]
     pub escrow_id: String,
     /// Sui address funds release/refund to (32-byte hex).
     pub recipient: String,
     /// 16-byte nonce as 32-char hex (with or without `0x` prefix).
     pub nonce: String,
     /// Hard wall-clock deadline after which the signature is invalid.
+    #[serde(alias = "expiryMs")]
     pub expiry_ms: u64,
 }
 
@@ -395,23 +1097,579 @@ fn signing_message_v2(resp: &SignableOrderResponse, v2: &OrderV2Fields) -> Resul
 }
 
 /// Sign a V2 message. Returned base64 signature is over the canonical V2
-/// bytes; backend verifies via the same enclave master public key as V1.
-pub fn sign_v2(resp: &SignableOrderResponse, v2: &OrderV2Fields) -> Result<String, String> {
+/// bytes; backend verifies via the same public key as V1.
+pub fn sign_v2(
+    resp: &SignableOrderResponse,
+    v2: &OrderV2Fields,
+    scope: KeyScope,
+) -> Result<String, String> {
     let msg = signing_message_v2(resp, v2)?;
     info!(
         "Signing V2 message of {} bytes for order {}",
         msg.len(),
         resp.order_id
     );
-    let sig = crypto::sign(&msg);
+    let sig = crypto::sign_in(scope, &msg)?;
     Ok(B64.encode(sig))
 }
 
-fn unix_time_ms() -> u64 {
-    // Available with std; enclave has /dev/rtc/time source. Replace if monotonic-only.
-    use std::time::{SystemTime, UNIX_EPOCH};
-    SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .map(|d| d.as_millis() as u64)
-        .unwrap_or(0)
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn request(action: &str, settle_amount: Option<u64>) -> OrderRequest {
+        serde_json::from_value(json!({
+            "version": 1,
+            "order_id": "o1",
+            "customer": "alice",
+            "merchant": "acme",
+            "amount": 100,
+            "currency": "USD",
+            "action": action,
+            "settle_amount": settle_amount,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn cancel_round_trips_through_serde() {
+        assert_eq!(serde_json::to_value(OrderAction::Cancel).unwrap(), "cancel");
+        assert_eq!(
+            serde_json::from_value::<OrderAction>(json!("cancel")).unwrap(),
+            OrderAction::Cancel
+        );
+        assert_eq!(
+            serde_json::to_value(OrderStatus::Cancelled).unwrap(),
+            "cancelled"
+        );
+        assert_eq!(
+            serde_json::from_value::<OrderStatus>(json!("cancelled")).unwrap(),
+            OrderStatus::Cancelled
+        );
+    }
+
+    #[test]
+    fn existing_actions_keep_their_signed_bytes() {
+        use OrderAction::*;
+        let actions = [Initiate, Deposit, Release, Refund, Cancel];
+        for (byte, action) in actions.into_iter().enumerate() {
+            assert_eq!(action.to_intent(), byte as u8);
+            assert_eq!(action.to_u8(), byte as u8);
+            assert_eq!(action.to_intent_v2(), 0x10 + byte as u8);
+            assert_eq!(bcs::to_bytes(&action).unwrap(), [byte as u8]);
+        }
+    }
+
+    #[test]
+    fn next_status_follows_the_escrow_state_machine() {
+        use OrderAction::*;
+        use OrderStatus::*;
+        assert_eq!(next_status(None, Initiate), Ok(Pending));
+        assert_eq!(next_status(Some(Pending), Deposit), Ok(Escrowed));
+        assert_eq!(next_status(Some(Escrowed), Release), Ok(Released));
+        assert_eq!(next_status(Some(Escrowed), Refund), Ok(Refunded));
+        assert_eq!(next_status(Some(Pending), Cancel), Ok(Cancelled));
+        assert!(next_status(None, Deposit).is_err());
+        assert!(next_status(Some(Escrowed), Cancel).is_err());
+        assert!(next_status(Some(Released), Release).is_err());
+    }
+
+    #[test]
+    fn each_legal_path_reaches_its_final_status() {
+        use OrderStatus::*;
+        let paths: [&[(&str, OrderStatus)]; 3] = [
+            &[
+                ("initiate", Pending),
+                ("deposit", Escrowed),
+                ("release", Released),
+            ],
+            &[
+                ("initiate", Pending),
+                ("deposit", Escrowed),
+                ("refund", Refunded),
+            ],
+            &[("initiate", Pending), ("cancel", Cancelled)],
+        ];
+        for path in paths {
+            let (mut prior, mut escrowed) = (None, None);
+            for (action, expected) in path {
+                let req = request(action, None);
+                let resp = process_order(&req, prior, None, escrowed, 60_000).unwrap();
+                assert_eq!(resp.status, *expected, "{} after {:?}", action, prior);
+                assert_eq!(resp.notes, None);
+                prior = Some(resp.status);
+                escrowed = resp.remaining_amount.or(escrowed);
+            }
+        }
+    }
+
+    #[test]
+    fn expires_at_ms_round_trips_through_serde() {
+        let req: OrderRequest = serde_json::from_value(json!({
+            "version": 1,
+            "order_id": "o1",
+            "customer": "alice",
+            "merchant": "acme",
+            "amount": 100,
+            "currency": "USD",
+            "action": "initiate",
+            "expires_at_ms": 1_700_000_060_000u64,
+        }))
+        .unwrap();
+        assert_eq!(req.expires_at_ms, Some(1_700_000_060_000));
+        let again: OrderRequest =
+            serde_json::from_value(serde_json::to_value(&req).unwrap()).unwrap();
+        assert_eq!(again.expires_at_ms, req.expires_at_ms);
+
+        let mut resp = vector_response();
+        resp.expires_at_ms = Some(1_700_000_060_000);
+        let json = serde_json::to_value(&resp).unwrap();
+        assert_eq!(json["expires_at_ms"], 1_700_000_060_000u64);
+        let again: SignableOrderResponse = serde_json::from_value(json).unwrap();
+        assert_eq!(again.expires_at_ms, resp.expires_at_ms);
+        assert_ne!(
+            signing_message(&again).unwrap(),
+            signing_message(&vector_response()).unwrap()
+        );
+    }
+
+    #[test]
+    fn actions_past_expiry_are_refused() {
+        use OrderAction::*;
+        let expiry = Some(1_000);
+        for action in [Initiate, Deposit, Release, Refund, Cancel] {
+            assert!(check_expiry(action, expiry, 1_000).is_ok());
+            assert!(check_expiry(action, None, 5_000).is_ok());
+        }
+        for action in [Initiate, Deposit, Release, Refund] {
+            assert!(check_expiry(action, expiry, 1_001).is_err());
+        }
+        assert!(check_expiry(Cancel, expiry, 1_001).is_ok());
+
+        // An order whose expiry has passed is signed as rejected.
+        let req = request("deposit", None);
+        let resp = process_order(&req, Some(OrderStatus::Pending), Some(1), None, 60_000).unwrap();
+        assert_eq!(resp.status, OrderStatus::Rejected);
+        assert_eq!(resp.notes.as_deref(), Some("order expired at 1 ms"));
+        assert_eq!(resp.expires_at_ms, Some(1));
+    }
+
+    #[test]
+    fn partial_release_keeps_the_order_escrowed() {
+        let req = request("release", Some(40));
+        let settlement = settle(&req, OrderStatus::Released, Some(100)).unwrap();
+        assert_eq!(
+            settlement,
+            Settlement {
+                status: OrderStatus::Escrowed,
+                remaining: Some(60),
+                settled: Some(40),
+            }
+        );
+    }
+
+    #[test]
+    fn two_partial_releases_sum_back_to_the_amount() {
+        let first = request("release", Some(40));
+        let resp =
+            process_order(&first, Some(OrderStatus::Escrowed), None, Some(100), 60_000).unwrap();
+        assert_eq!(resp.status, OrderStatus::Escrowed);
+        assert_eq!(resp.remaining_amount, Some(60));
+
+        let second = request("release", Some(60));
+        let last = process_order(
+            &second,
+            Some(resp.status),
+            None,
+            resp.remaining_amount,
+            60_000,
+        )
+        .unwrap();
+        assert_eq!(last.status, OrderStatus::Released);
+        assert_eq!(last.remaining_amount, Some(0));
+        assert_eq!(
+            resp.settle_amount.unwrap() + last.settle_amount.unwrap(),
+            first.amount
+        );
+    }
+
+    #[test]
+    fn release_without_settle_amount_drains_the_balance() {
+        let req = request("release", None);
+        let settlement = settle(&req, OrderStatus::Released, Some(60)).unwrap();
+        assert_eq!(settlement.status, OrderStatus::Released);
+        assert_eq!(settlement.remaining, Some(0));
+        assert_eq!(settlement.settled, Some(60));
+    }
+
+    #[test]
+    fn over_settlement_is_refused() {
+        let req = request("refund", Some(61));
+        assert!(settle(&req, OrderStatus::Refunded, Some(60)).is_err());
+        let resp =
+            process_order(&req, Some(OrderStatus::Escrowed), None, Some(60), 60_000).unwrap();
+        assert_eq!(resp.status, OrderStatus::Rejected);
+        assert_eq!(
+            resp.notes.as_deref(),
+            Some("settle_amount: 61 exceeds the balance of 60")
+        );
+    }
+
+    #[test]
+    fn illegal_transition_is_signed_as_rejected() {
+        let req = request("release", None);
+        let resp = process_order(&req, Some(OrderStatus::Pending), None, None, 60_000).unwrap();
+        assert_eq!(resp.status, OrderStatus::Rejected);
+        assert!(resp.notes.is_some());
+        assert_eq!(resp.settle_amount, None);
+    }
+
+    #[test]
+    fn settle_amount_is_part_of_the_signing_message() {
+        let req = request("release", Some(40));
+        let resp =
+            process_order(&req, Some(OrderStatus::Escrowed), None, Some(100), 60_000).unwrap();
+        assert_eq!(resp.settle_amount, Some(40));
+        let mut other = resp.clone();
+        other.settle_amount = Some(41);
+        assert_ne!(
+            signing_message(&resp).unwrap(),
+            signing_message(&other).unwrap()
+        );
+    }
+
+    fn fixed_seed(seed: &mut [u8; 32]) -> Result<(), &'static str> {
+        seed.fill(7);
+        Ok(())
+    }
+
+    fn signed_response() -> SignedOrderResponse {
+        crypto::ensure_initialized_with(crypto::SchemeKind::Ed25519, fixed_seed).unwrap();
+        let req = request("initiate", None);
+        let resp = process_order(&req, None, None, None, 60_000).unwrap();
+        sign_response(&resp).unwrap()
+    }
+
+    #[test]
+    fn enclave_signature_verifies_with_and_without_kid() {
+        let mut signed = signed_response();
+        assert_eq!(verify_signed_response(&signed), Ok(true));
+        signed.kid.clear();
+        assert_eq!(verify_signed_response(&signed), Ok(true));
+    }
+
+    #[test]
+    fn recomputed_signing_message_verifies() {
+        let signed = signed_response();
+        let message = signing_message(&signed.response).unwrap();
+        let signature: [u8; 64] = B64.decode(&signed.signature).unwrap().try_into().unwrap();
+        assert_eq!(
+            crypto::verify(&message, &signature, &signed.public_key),
+            Ok(true)
+        );
+    }
+
+    #[test]
+    fn public_key_fingerprint_matches_the_full_key() {
+        use sha2::{Digest, Sha256};
+        let signed = signed_response();
+        let key = B64.decode(&signed.public_key).unwrap();
+        let expected = Hex::encode(&Sha256::digest(&key)[..8]);
+        assert_eq!(signed.public_key_fingerprint, expected);
+        assert_eq!(signed.public_key_fingerprint.len(), 16);
+    }
+
+    #[test]
+    fn signature_under_a_foreign_key_is_untrusted() {
+        use ed25519_dalek::Signer;
+        let mut signed = signed_response();
+        let foreign = ed25519_dalek::SigningKey::from_bytes(&[9; 32]);
+        let message = signing_message(&signed.response).unwrap();
+        signed.signature = B64.encode(foreign.sign(&message).to_bytes());
+        signed.public_key = B64.encode(foreign.verifying_key().to_bytes());
+        signed.kid.clear();
+        assert_eq!(verify_signed_response(&signed), Err("untrusted_key"));
+    }
+
+    fn vector_response() -> SignableOrderResponse {
+        SignableOrderResponse {
+            field_version: SIGNING_FIELD_VERSION,
+            version: 1,
+            order_id: "o1".to_string(),
+            action: OrderAction::Initiate,
+            status: OrderStatus::Pending,
+            amount: 100,
+            currency: "USD".to_string(),
+            server_timestamp_ms: 1_700_000_000_000,
+            escrow_tx_id: None,
+            notes: None,
+            client_reference: None,
+            sequence: 1,
+            expires_at_ms: None,
+            remaining_amount: None,
+            request_digest: String::new(),
+            fee_amount: 0,
+            net_amount: 100,
+            metadata_digest: None,
+            settle_amount: None,
+        }
+    }
+
+    /// BCS signing message of `vector_response`: intent, timestamp_ms, then
+    /// the payload opening with field_version.
+    const BCS_VECTOR_HEX: &str = concat!(
+        "00",
+        "68e5cf8b01000000",
+        "09",
+        "01",
+        "026f31",
+        "00",
+        "00",
+        "6400000000000000",
+        "03555344",
+        "68e5cf8b01000000",
+        "000000",
+        "0100000000000000",
+        "0000",
+        "00",
+        "0000000000000000",
+        "6400000000000000",
+        "0000",
+    );
+
+    /// Canonical-JSON signing message of `vector_response`.
+    const JCS_VECTOR: &str = concat!(
+        "nautilus/order/jcs/v5/p1",
+        r#"{"action":"initiate","amount":100,"client_reference":null,"#,
+        r#""currency":"USD","escrow_tx_id":null,"expires_at_ms":null,"#,
+        r#""fee_amount":0,"field_version":9,"net_amount":100,"notes":null,"#,
+        r#""order_id":"o1","remaining_amount":null,"request_digest":"","#,
+        r#""sequence":1,"server_timestamp_ms":1700000000000,"#,
+        r#""settle_amount":null,"status":"pending","version":1}"#,
+    );
+
+    #[test]
+    fn bcs_signing_message_matches_the_golden_layout() {
+        assert_eq!(SIGNING_FIELD_VERSION, 9);
+        let message = signing_message(&vector_response()).unwrap();
+        assert_eq!(Hex::encode(&message), BCS_VECTOR_HEX);
+        assert_eq!(message[9], SIGNING_FIELD_VERSION);
+    }
+
+    #[test]
+    fn jcs_signing_message_matches_the_fixed_vector() {
+        let message = signing_message_for(&vector_response(), SigningFormat::Jcs).unwrap();
+        assert_eq!(String::from_utf8(message).unwrap(), JCS_VECTOR);
+    }
+
+    #[test]
+    fn both_formats_are_stable_and_distinct() {
+        let resp = vector_response();
+        let bcs = signing_message_for(&resp, SigningFormat::Bcs).unwrap();
+        let jcs = signing_message_for(&resp, SigningFormat::Jcs).unwrap();
+        assert_eq!(Hex::encode(&bcs), BCS_VECTOR_HEX);
+        assert_eq!(jcs, JCS_VECTOR.as_bytes());
+        assert_ne!(bcs, jcs);
+        assert!(jcs.starts_with(JCS_DOMAIN_TAG));
+    }
+
+    #[test]
+    fn signatures_verify_under_the_chosen_format() {
+        crypto::ensure_initialized_with(crypto::SchemeKind::Ed25519, fixed_seed).unwrap();
+        for format in [SigningFormat::Bcs, SigningFormat::Jcs] {
+            let mut signed = sign_response_as(&vector_response(), format).unwrap();
+            assert_eq!(signed.alg, format.as_str());
+            assert_eq!(verify_signed_response(&signed), Ok(true));
+            // The same signature does not cover the other format's message.
+            signed.alg = match format {
+                SigningFormat::Bcs => SigningFormat::Jcs,
+                SigningFormat::Jcs => SigningFormat::Bcs,
+            }
+            .as_str()
+            .to_string();
+            assert_eq!(verify_signed_response(&signed), Ok(false));
+        }
+    }
+
+    #[test]
+    fn both_digest_paths_verify() {
+        crypto::ensure_initialized_with(crypto::SchemeKind::Ed25519, fixed_seed).unwrap();
+        let resp = vector_response();
+        let full =
+            sign_response_hashed(&resp, SigningFormat::Bcs, KeyScope::Enclave, false).unwrap();
+        assert_eq!(full.digest, None);
+        assert_eq!(verify_signed_response(&full), Ok(true));
+
+        let hashed =
+            sign_response_hashed(&resp, SigningFormat::Bcs, KeyScope::Enclave, true).unwrap();
+        let message = signing_message(&hashed.response).unwrap();
+        assert_eq!(hashed.digest, Some(Hex::encode(message_digest(&message))));
+        assert_eq!(verify_signed_response(&hashed), Ok(true));
+        assert_ne!(hashed.signature, full.signature);
+    }
+
+    #[test]
+    fn tampering_with_any_request_field_changes_its_digest() {
+        let base = request("release", Some(40));
+        let digest = request_digest(&base).unwrap();
+        assert_eq!(make_response(&base).unwrap().request_digest, digest);
+
+        let tampers: [fn(&mut OrderRequest); 8] = [
+            |r| r.version = 2,
+            |r| r.order_id.push('x'),
+            |r| r.customer.push('x'),
+            |r| r.merchant.push('x'),
+            |r| r.amount += 1,
+            |r| r.currency = "EUR".to_string(),
+            |r| r.action = OrderAction::Refund,
+            |r| r.settle_amount = Some(41),
+        ];
+        for tamper in tampers {
+            let mut req = base.clone();
+            tamper(&mut req);
+            assert_ne!(request_digest(&req).unwrap(), digest, "{:?}", req);
+        }
+    }
+
+    /// Stands in for a response that cannot be encoded.
+    struct Unencodable;
+
+    impl Serialize for Unencodable {
+        fn serialize<S: serde::Serializer>(&self, _: S) -> Result<S::Ok, S::Error> {
+            Err(serde::ser::Error::custom("unencodable field"))
+        }
+    }
+
+    #[test]
+    fn encoding_failure_is_a_clean_500() {
+        for err in [
+            bcs::to_bytes(&Unencodable).map_err(|e| encoding_failed("BCS", e)),
+            serde_json::to_vec(&Unencodable).map_err(|e| encoding_failed("JSON", e)),
+        ] {
+            let (status, body) = err.unwrap_err().into_status_and_body();
+            assert_eq!(status, axum::http::StatusCode::INTERNAL_SERVER_ERROR);
+            assert_eq!(body["code"], "internal_error");
+            assert!(body["message"]
+                .as_str()
+                .unwrap()
+                .ends_with("encoding failed: unencodable field"));
+        }
+    }
+
+    #[test]
+    fn metadata_is_signed_as_its_digest() {
+        let mut req = request("initiate", None);
+        assert_eq!(make_response(&req).unwrap().metadata_digest, None);
+
+        req.metadata = Some(json!({ "b": 1, "a": 2 }));
+        let expected = Hex::encode(Blake2b256::digest(br#"{"a":2,"b":1}"#).digest);
+        assert_eq!(
+            make_response(&req).unwrap().metadata_digest,
+            Some(expected.clone())
+        );
+        req.metadata = Some(json!({ "b": 1, "a": 3 }));
+        assert_ne!(make_response(&req).unwrap().metadata_digest, Some(expected));
+    }
+
+    #[test]
+    fn jcs_refuses_integers_beyond_2_pow_53() {
+        let mut resp = vector_response();
+        resp.amount = MAX_SAFE_INTEGER;
+        assert!(signing_message_for(&resp, SigningFormat::Jcs).is_ok());
+        resp.amount = MAX_SAFE_INTEGER + 1;
+        assert!(signing_message_for(&resp, SigningFormat::Jcs).is_err());
+        assert!(signing_message_for(&resp, SigningFormat::Bcs).is_ok());
+    }
+
+    #[test]
+    fn only_supported_versions_are_accepted() {
+        assert!(check_version(1).is_ok());
+        match check_version(99) {
+            Err(EnclaveError::InvalidRequest {
+                code: "unsupported_version",
+                message,
+            }) => assert!(message.ends_with("supported versions: [1]"), "{}", message),
+            other => panic!("expected unsupported_version, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn each_version_signs_under_its_own_tag() {
+        assert_eq!(jcs_domain_tag(1), b"nautilus/order/jcs/v5/p1");
+        assert_ne!(jcs_domain_tag(1), jcs_domain_tag(2));
+
+        let mut resp = vector_response();
+        let v1 = signing_message_for(&resp, SigningFormat::Jcs).unwrap();
+        resp.version = 2;
+        let v2 = signing_message_for(&resp, SigningFormat::Jcs).unwrap();
+        assert!(v1.starts_with(&jcs_domain_tag(1)));
+        assert!(v2.starts_with(&jcs_domain_tag(2)));
+    }
+
+    #[test]
+    fn unsafe_amounts_are_refused_only_under_jcs() {
+        let mut req = request("release", Some(MAX_SAFE_INTEGER + 1));
+        assert!(check_safe_amounts(&req, SigningFormat::Bcs).is_ok());
+        assert!(matches!(
+            check_safe_amounts(&req, SigningFormat::Jcs),
+            Err(EnclaveError::InvalidRequest {
+                code: "amount_exceeds_safe_integer",
+                ..
+            })
+        ));
+        req.settle_amount = Some(1);
+        assert!(check_safe_amounts(&req, SigningFormat::Jcs).is_ok());
+    }
+
+    #[test]
+    fn signing_message_bound_is_enforced() {
+        // Metadata is only signed as its digest, so a long client_reference
+        // is what can push the message past the bound.
+        let mut resp = vector_response();
+        let len = signing_message(&resp).unwrap().len();
+        assert!(check_signing_message_size(&resp, SigningFormat::Bcs, len).is_ok());
+        resp.client_reference = Some("r".repeat(64));
+        assert!(matches!(
+            check_signing_message_size(&resp, SigningFormat::Bcs, len + 32),
+            Err(EnclaveError::InvalidRequest {
+                code: "signing_message_too_large",
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn allocated_order_id_is_carried_into_the_response() {
+        let id = allocate_order_id().unwrap();
+        assert_eq!(URL_SAFE_NO_PAD.decode(&id).unwrap().len(), 16);
+        assert_ne!(allocate_order_id().unwrap(), id);
+        let mut req = request("initiate", None);
+        req.order_id = id.clone();
+        let resp = process_order(&req, None, None, None, 60_000).unwrap();
+        assert_eq!(resp.order_id, id);
+        crypto::ensure_initialized_with(crypto::SchemeKind::Ed25519, fixed_seed).unwrap();
+        let signed = sign_response(&resp).unwrap();
+        assert_eq!(signed.response.order_id, id);
+    }
+
+    #[test]
+    fn client_reference_round_trips_under_the_signature() {
+        crypto::ensure_initialized_with(crypto::SchemeKind::Ed25519, fixed_seed).unwrap();
+        let mut req = request("initiate", None);
+        req.client_reference = Some("INV-2024-001".to_string());
+        let resp = process_order(&req, None, None, None, 60_000).unwrap();
+        assert_eq!(resp.client_reference.as_deref(), Some("INV-2024-001"));
+        let mut signed = sign_response(&resp).unwrap();
+        assert_eq!(verify_signed_response(&signed), Ok(true));
+        signed.response.client_reference = Some("INV-2024-002".to_string());
+        assert_eq!(verify_signed_response(&signed), Ok(false));
+    }
+
+    #[test]
+    fn unknown_kid_is_refused() {
+        let mut signed = signed_response();
+        signed.kid = "0000000000000000".to_string();
+        assert_eq!(verify_signed_response(&signed), Err("unknown_kid"));
+    }
 }
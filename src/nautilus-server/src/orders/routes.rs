@@ -0,0 +1,945 @@
+// Copyright (c), Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! HTTP handlers for the order routes mounted by `crate::build_router`.
+
+use crate::orders::crypto::KeyScope;
+use crate::orders::negotiate::OrderBody;
+use crate::{common, metrics, orders, AppState, EnclaveError};
+use axum::{
+    extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use fastcrypto::encoding::{Encoding, Hex};
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Instant;
+use tracing::{info, warn};
+
+/// Query parameters of `POST /orders/process`.
+#[derive(Debug, Deserialize)]
+pub struct ProcessParams {
+    /// `cose` additionally returns the signature as a COSE_Sign1 envelope.
+    pub format: Option<String>,
+}
+
+impl ProcessParams {
+    /// Whether a COSE_Sign1 envelope was asked for.
+    fn emit_cose(&self) -> Result<bool, EnclaveError> {
+        match self.format.as_deref() {
+            None | Some("json") => Ok(false),
+            Some("cose") => Ok(true),
+            Some(other) => Err(EnclaveError::InvalidRequest {
+                code: "unsupported_format",
+                message: format!("unknown response format {:?}", other),
+            }),
+        }
+    }
+}
+
+/// One slot of a `/orders/process_batch` response.
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+pub enum BatchResult {
+    Ok(orders::SignedOrderResponse),
+    /// `error` has the same shape as a single-order error body.
+    Err {
+        order_id: String,
+        error: serde_json::Value,
+    },
+}
+
+/// `POST /orders/process`: sign one order. The body may be JSON or BCS, and
+/// the response is BCS when `Accept` asks for `application/bcs`. A retry
+/// carrying the same `Idempotency-Key` gets the cached response back.
+pub async fn process_order_http(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<ProcessParams>,
+    headers: HeaderMap,
+    OrderBody {
+        body: req,
+        respond_bcs,
+    }: OrderBody<orders::OrderRequest>,
+) -> Result<Response, EnclaveError> {
+    state.dead_man.ensure_armed()?;
+    let emit_cose = params.emit_cose()?;
+    let idempotency = match orders::idempotency::key_from_headers(&headers)? {
+        Some(key) => Some((key, orders::idempotency::fingerprint(&req, emit_cose)?)),
+        None => None,
+    };
+    let cached = match &idempotency {
+        Some((key, fingerprint)) => state.idempotency.lookup(key, fingerprint)?,
+        None => None,
+    };
+    let signed = match cached {
+        Some(signed) => {
+            info!(order_id = %signed.response.order_id, "Returning cached idempotent response");
+            signed
+        }
+        None => {
+            let dry_run = req.dry_run;
+            let signed = process_one(&state, req, emit_cose).await?;
+            if let Some((key, fingerprint)) = idempotency.filter(|_| !dry_run) {
+                state.idempotency.store(key, fingerprint, signed.clone());
+            }
+            signed
+        }
+    };
+    if respond_bcs {
+        return orders::negotiate::bcs_response(&signed, state.orders_config.signing_format);
+    }
+    Ok(Json(orders::naming::render(
+        &signed,
+        state.orders_config.field_naming,
+    )?)
+    .into_response())
+}
+
+/// `POST /orders/process_batch`: process each order independently, in
+/// order. A failing order fills its slot with an error instead of failing
+/// the batch. The body may be JSON or BCS; the response is always JSON.
+pub async fn process_batch_http(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<ProcessParams>,
+    OrderBody { body: reqs, .. }: OrderBody<Vec<orders::OrderRequest>>,
+) -> Result<Json<serde_json::Value>, EnclaveError> {
+    state.dead_man.ensure_armed()?;
+    let emit_cose = params.emit_cose()?;
+    let max = state.orders_config.max_batch_size;
+    if reqs.len() > max {
+        return Err(EnclaveError::TooLarge {
+            code: "batch_too_large",
+            message: format!("batch has {} orders, limit is {}", reqs.len(), max),
+        });
+    }
+    info!(orders = reqs.len(), "Processing order batch");
+    let mut results = Vec::with_capacity(reqs.len());
+    for req in reqs {
+        let order_id = req.order_id.clone();
+        results.push(match process_one(&state, req, emit_cose).await {
+            Ok(signed) => BatchResult::Ok(signed),
+            Err(e) => BatchResult::Err {
+                order_id,
+                error: e.into_status_and_body().1,
+            },
+        });
+    }
+    Ok(Json(orders::naming::render(
+        &results,
+        state.orders_config.field_naming,
+    )?))
+}
+
+/// Every check an order must pass before it is processed: protocol
+/// version, field validation, configured limits and the order's fixed
+/// terms. Shared by `/orders/process` and `/orders/preview`, so a preview
+/// is refused whenever processing would be.
+fn check_request(
+    config: &orders::OrdersConfig,
+    store: &orders::store::OrderStore,
+    req: &orders::OrderRequest,
+) -> Result<(), EnclaveError> {
+    orders::order::check_version(req.version)?;
+    req.validate()?;
+    orders::order::check_safe_amounts(req, config.signing_format)?;
+    if let Some(pinned) = config.strict_protocol_version {
+        if req.version != pinned {
+            return Err(EnclaveError::InvalidRequest {
+                code: "protocol_version_mismatch",
+                message: format!(
+                    "protocol version {} is not the pinned version {}",
+                    req.version, pinned
+                ),
+            });
+        }
+    }
+    if config.per_merchant_keys && !config.merchant_allowlist.contains(&req.merchant) {
+        return Err(EnclaveError::InvalidRequest {
+            code: "unknown_merchant",
+            message: format!("merchant {:?} has no signing key", req.merchant),
+        });
+    }
+    // Compared trimmed and case-insensitively, so "Acme " and "acme" count
+    // as the same party.
+    if !config.allow_self_dealing
+        && req
+            .customer
+            .trim()
+            .eq_ignore_ascii_case(req.merchant.trim())
+    {
+        return Err(EnclaveError::InvalidRequest {
+            code: "self_dealing",
+            message: "customer and merchant must be different parties".to_string(),
+        });
+    }
+    if config.reject_reserved_metadata_keys {
+        orders::metadata::check_reserved_keys(req.metadata.as_ref())?;
+    }
+    orders::metadata::check_element_count(req.metadata.as_ref(), config.max_metadata_elements)?;
+    orders::metadata::check_depth(req.metadata.as_ref(), config.max_metadata_depth)?;
+    orders::metadata::check_size(req.metadata.as_ref(), config.max_metadata_bytes)?;
+    if let Some(reference) = &req.client_reference {
+        if reference.len() > config.max_client_reference_bytes {
+            return Err(EnclaveError::InvalidRequest {
+                code: "client_reference_too_long",
+                message: format!(
+                    "client_reference is {} bytes, limit is {}",
+                    reference.len(),
+                    config.max_client_reference_bytes
+                ),
+            });
+        }
+    }
+    // Amount, currency and parties are fixed by the order's Initiate; a
+    // request for the same id that disagrees is a client bug or an attack.
+    if let Some(terms) = store.terms(&req.order_id) {
+        terms.check(req)?;
+    }
+    Ok(())
+}
+
+/// Validate, sign and record a single order. A dry run is signed but not
+/// recorded.
+async fn process_one(
+    state: &AppState,
+    mut req: orders::OrderRequest,
+    emit_cose: bool,
+) -> Result<orders::SignedOrderResponse, EnclaveError> {
+    let config = &state.orders_config;
+    if config.allocate_order_ids && req.order_id.is_empty() {
+        if !matches!(req.action, orders::OrderAction::Initiate) {
+            return Err(EnclaveError::InvalidRequest {
+                code: "missing_order_id",
+                message: "only Initiate may omit order_id".to_string(),
+            });
+        }
+        req.order_id = orders::order::allocate_order_id()
+            .map_err(|e| EnclaveError::GenericError(format!("order id allocation: {}", e)))?;
+        info!(order_id = %req.order_id, "Allocated order id");
+    }
+    info!(
+        order_id = %req.order_id,
+        action = ?req.action,
+        amount = req.amount,
+        currency = %req.currency,
+        "Processing order request"
+    );
+    check_request(config, &state.order_store, &req)?;
+    let prior = state.order_store.get(&req.order_id);
+    let escrowed = state.order_store.remaining_amount(&req.order_id);
+    let expires_at_ms =
+        orders::order::effective_expiry(&req, state.order_store.expires_at_ms(&req.order_id));
+    let mut resp = orders::process_order(
+        &req,
+        prior,
+        expires_at_ms,
+        escrowed,
+        config.max_clock_skew_ms,
+    )?;
+    orders::order::apply_fee(&mut resp, config.fee_bps);
+    if req.dry_run {
+        resp.notes = Some(match resp.notes.take() {
+            Some(notes) => format!("dry run: {}", notes),
+            None => "dry run".to_string(),
+        });
+    }
+    info!(order_id = %resp.order_id, status = ?resp.status, "Generated response");
+    orders::order::check_signing_message_size(
+        &resp,
+        config.signing_format,
+        config.max_signing_message_bytes,
+    )?;
+    if req.dry_run {
+        info!(order_id = %resp.order_id, "Dry run; leaving order state untouched");
+        return sign_one(state, &req, &resp, emit_cose).await;
+    }
+    orders::limits::check_state_budget(
+        state.spending_windows.approx_bytes()
+            + state.replay_guard.approx_bytes()
+            + state.order_store.approx_bytes()
+            + state.idempotency.approx_bytes(),
+        config.max_state_bytes,
+    )?;
+    let mut recorded = Recorded::default();
+    if let Err(e) = record_state(state, &req, &resp, prior, escrowed, &mut recorded) {
+        recorded.undo(state, &req, &resp);
+        return Err(e);
+    }
+    // A sequence number burnt by a failed signing leaves a gap; sequences
+    // stay strictly increasing, which is all they promise.
+    resp.sequence = state.sequence.fetch_add(1, Ordering::SeqCst) + 1;
+    let signed = match sign_one(state, &req, &resp, emit_cose).await {
+        Ok(signed) => signed,
+        Err(e) => {
+            warn!(order_id = %resp.order_id, error = %e, "Signing failed; undoing state changes");
+            recorded.undo(state, &req, &resp);
+            return Err(e);
+        }
+    };
+    state.order_store.record_signed(&signed);
+    if let Some(audit_log) = &state.audit_log {
+        audit_log.record(&signed);
+    }
+    if let Some(webhook) = &state.webhook {
+        webhook.notify(&signed);
+    }
+    metrics::record_order(resp.action.as_str(), resp.status.as_str());
+    metrics::set_order_store_size(state.order_store.len());
+    Ok(signed)
+}
+
+/// State changes `record_state` has made for one request, so they can be
+/// undone if the request fails before its response is signed. Otherwise
+/// the order would have moved on with no signature ever returned for it,
+/// and every retry would be refused as a replay or illegal transition.
+#[derive(Default)]
+struct Recorded {
+    action_counted: bool,
+    /// Escrowed balance the replay guard recorded the settlement from.
+    replayed_from: Option<Option<u64>>,
+    spend_recorded: bool,
+    /// `(prior, prior_remaining)` the order was moved from.
+    transitioned_from: Option<(Option<orders::OrderStatus>, Option<u64>)>,
+}
+
+impl Recorded {
+    fn undo(
+        self,
+        state: &AppState,
+        req: &orders::OrderRequest,
+        resp: &orders::SignableOrderResponse,
+    ) {
+        if let Some((prior, prior_remaining)) = self.transitioned_from {
+            if !state.order_store.revert(
+                &resp.order_id,
+                resp.status,
+                resp.remaining_amount,
+                prior,
+                prior_remaining,
+            ) {
+                warn!(order_id = %resp.order_id, "Order moved on; transition not reverted");
+            }
+        }
+        if self.spend_recorded {
+            state.spending_windows.forget(
+                &req.customer,
+                &req.currency,
+                req.amount,
+                resp.server_timestamp_ms,
+            );
+        }
+        if let Some(remaining_before) = self.replayed_from {
+            state
+                .replay_guard
+                .forget(&resp.order_id, req.action, remaining_before);
+        }
+        if self.action_counted {
+            state.order_store.forget_action(&resp.order_id);
+        }
+    }
+}
+
+/// Apply the limits and the state transition for `resp`, noting each change
+/// in `recorded` as it is made.
+fn record_state(
+    state: &AppState,
+    req: &orders::OrderRequest,
+    resp: &orders::SignableOrderResponse,
+    prior: Option<orders::OrderStatus>,
+    escrowed: Option<u64>,
+    recorded: &mut Recorded,
+) -> Result<(), EnclaveError> {
+    state
+        .order_store
+        .try_count_action(&resp.order_id, state.orders_config.max_actions_per_order)?;
+    recorded.action_counted = true;
+    // A rejected transition is still signed but changes no state.
+    if resp.status == orders::OrderStatus::Rejected {
+        return Ok(());
+    }
+    // Keyed by the balance before the action, so each installment of a
+    // partial settlement is guarded on its own.
+    if !req.allow_replay {
+        state
+            .replay_guard
+            .try_record(&resp.order_id, req.action, escrowed)?;
+        recorded.replayed_from = Some(escrowed);
+    }
+    if matches!(req.action, orders::OrderAction::Initiate) {
+        state.spending_windows.try_record(
+            &req.customer,
+            &req.currency,
+            req.amount,
+            resp.server_timestamp_ms,
+        )?;
+        recorded.spend_recorded = true;
+    }
+    state.order_store.compare_and_set(
+        &resp.order_id,
+        prior,
+        escrowed,
+        resp.status,
+        resp.remaining_amount,
+    )?;
+    recorded.transitioned_from = Some((prior, escrowed));
+    if matches!(req.action, orders::OrderAction::Initiate) {
+        state
+            .order_store
+            .set_expires_at_ms(&resp.order_id, resp.expires_at_ms);
+        state
+            .order_store
+            .set_terms(&resp.order_id, orders::store::OrderTerms::of(req));
+    }
+    Ok(())
+}
+
+/// Sign `resp` for `req` with every signature the configuration asks for.
+/// Touches no state, so dry runs share it with real requests.
+async fn sign_one(
+    state: &AppState,
+    req: &orders::OrderRequest,
+    resp: &orders::SignableOrderResponse,
+    emit_cose: bool,
+) -> Result<orders::SignedOrderResponse, EnclaveError> {
+    let config = &state.orders_config;
+    // V2 shadow mode: when the request opts in, sign both V1 and V2 with the
+    // same enclave master key. Backend stores both signatures and verifies
+    // both independently. V2 fields default to None for backwards compat.
+    let started = Instant::now();
+    let scope = if config.per_merchant_keys {
+        KeyScope::Merchant(&req.merchant)
+    } else {
+        KeyScope::Enclave
+    };
+    let mut signed = orders::sign_response_with_v2(
+        resp,
+        req.v2.as_ref(),
+        config.signing_format,
+        scope,
+        config.hash_before_sign,
+    )?;
+    metrics::record_signing_latency(started.elapsed());
+    if emit_cose {
+        signed.cose_sign1 = Some(orders::order::cose_sign1(
+            resp,
+            config.signing_format,
+            scope,
+        )?);
+    }
+    if let Some(cosigner) = &state.operator_cosigner {
+        let message = orders::order::signing_message_for(resp, config.signing_format)?;
+        signed.operator_signature = Some(cosigner.cosign(&message).await?);
+    }
+    info!(
+        order_id = %signed.response.order_id,
+        public_key = %signed.public_key,
+        v2_signed = signed.signature_v2.is_some(),
+        "Signed response"
+    );
+    Ok(signed)
+}
+
+/// `POST /orders/verify`: check a `SignedOrderResponse` as returned by
+/// `/orders/process`. A response signed by a key this enclave does not hold
+/// is `false` with reason `untrusted_key`; malformed keys or signatures are
+/// a 400.
+pub async fn verify_order(Json(signed): Json<orders::SignedOrderResponse>) -> impl IntoResponse {
+    match orders::order::verify_signed_response(&signed) {
+        Ok(valid) => {
+            info!(order_id = %signed.response.order_id, valid, "Verified response");
+            let reason = (!valid).then_some("signature_mismatch");
+            (
+                StatusCode::OK,
+                Json(serde_json::json!({ "valid": valid, "reason": reason })),
+            )
+        }
+        Err(reason @ "untrusted_key") => {
+            warn!(order_id = %signed.response.order_id, "Response signed by an untrusted key");
+            (
+                StatusCode::OK,
+                Json(serde_json::json!({ "valid": false, "reason": reason })),
+            )
+        }
+        Err(reason) => (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({ "valid": false, "reason": reason })),
+        ),
+    }
+}
+
+/// `POST /orders/preview`: build the response an order would get and the
+/// exact bytes that would be signed, without signing or recording anything,
+/// so integrators can diff their own reconstruction against ours.
+pub async fn preview_order(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<orders::OrderRequest>,
+) -> Result<Json<serde_json::Value>, EnclaveError> {
+    let format = state.orders_config.signing_format;
+    check_request(&state.orders_config, &state.order_store, &req)?;
+    let mut resp = orders::process_order(
+        &req,
+        state.order_store.get(&req.order_id),
+        orders::order::effective_expiry(&req, state.order_store.expires_at_ms(&req.order_id)),
+        state.order_store.remaining_amount(&req.order_id),
+        state.orders_config.max_clock_skew_ms,
+    )?;
+    orders::order::apply_fee(&mut resp, state.orders_config.fee_bps);
+    // Provisional: a concurrent order may take this sequence first.
+    resp.sequence = state.sequence.load(Ordering::SeqCst) + 1;
+    let message = orders::order::signing_message_for(&resp, format)?;
+    info!(order_id = %resp.order_id, bytes = message.len(), "Previewed signing message");
+    let preview = serde_json::json!({
+        "response": resp,
+        "alg": format.as_str(),
+        "signing_message_hex": Hex::encode(&message),
+    });
+    Ok(Json(orders::naming::render(
+        &preview,
+        state.orders_config.field_naming,
+    )?))
+}
+
+/// `GET /orders/:order_id`: the last response signed for the order, exactly
+/// as it was returned, so its signature still verifies.
+pub async fn get_order(
+    State(state): State<Arc<AppState>>,
+    Path(order_id): Path<String>,
+) -> Result<Json<serde_json::Value>, EnclaveError> {
+    let signed = state
+        .order_store
+        .last_signed(&order_id)
+        .ok_or_else(|| EnclaveError::NotFound(format!("order {} not found", order_id)))?;
+    Ok(Json(orders::naming::render(
+        &signed,
+        state.orders_config.field_naming,
+    )?))
+}
+
+/// Default and largest page size of `GET /orders/recent`.
+const RECENT_DEFAULT_LIMIT: usize = 50;
+const RECENT_MAX_LIMIT: usize = 500;
+
+/// Query parameters of `GET /orders/recent`.
+#[derive(Debug, Deserialize)]
+pub struct RecentParams {
+    pub limit: Option<usize>,
+    /// Only orders whose last response has a lower sequence; pass the
+    /// previous page's `next_before_seq` to page back.
+    pub before_seq: Option<u64>,
+}
+
+/// `GET /orders/recent`: the latest signed response of the most recently
+/// signed orders, by descending sequence. Admin only, since it lists every
+/// order the enclave still tracks.
+pub async fn recent_orders(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Query(params): Query<RecentParams>,
+) -> Result<Json<serde_json::Value>, EnclaveError> {
+    orders::admin::require_admin(&headers, state.orders_config.admin_token.as_ref())?;
+    let limit = params
+        .limit
+        .unwrap_or(RECENT_DEFAULT_LIMIT)
+        .min(RECENT_MAX_LIMIT);
+    let recent = state.order_store.recent(limit, params.before_seq);
+    // A short page is the last one.
+    let next_before_seq = recent
+        .last()
+        .filter(|_| recent.len() == limit)
+        .map(|signed| signed.response.sequence);
+    let page = serde_json::json!({
+        "orders": recent,
+        "next_before_seq": next_before_seq,
+    });
+    Ok(Json(orders::naming::render(
+        &page,
+        state.orders_config.field_naming,
+    )?))
+}
+
+/// `GET /orders/health`: the enclave key as `public_key_b64` with its
+/// `scheme`, any keys retired by rotation and, with per-merchant keys, the
+/// public key of every merchant that has had an order signed. Reports `ok`
+/// only if the key passes a sign-and-verify self-test, otherwise `unhealthy`
+/// with a 503.
+pub async fn orders_health(
+    State(state): State<Arc<AppState>>,
+) -> (StatusCode, Json<serde_json::Value>) {
+    if let Err(reason) = orders::crypto::self_test() {
+        warn!(reason, "Health check: signing key self-test failed");
+        return (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(serde_json::json!({ "status": "unhealthy", "reason": reason })),
+        );
+    }
+    let (Ok(pk_b64), Ok(scheme)) = (
+        orders::crypto::public_key_base64(),
+        orders::crypto::active_scheme(),
+    ) else {
+        return (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(serde_json::json!({ "status": "unhealthy" })),
+        );
+    };
+    info!(public_key = %pk_b64, "Health check");
+    let mut body = serde_json::json!({
+        "status": "ok",
+        "scheme": scheme.as_str(),
+        "public_key_b64": pk_b64,
+        "pcrs": common::pcr_measurements()
+    });
+    // Kept for clients written before secp256k1 keys, and only where the
+    // name is true.
+    if scheme == orders::crypto::SchemeKind::Ed25519 {
+        body["ed25519_pubkey_b64"] = serde_json::json!(pk_b64);
+    }
+    let retired = orders::crypto::retired_keys();
+    if !retired.is_empty() {
+        body["retired_keys"] = serde_json::json!(retired);
+    }
+    if state.orders_config.per_merchant_keys {
+        body["merchant_public_keys"] = serde_json::json!(orders::crypto::merchant_public_keys());
+        body["merchant_keys_digest"] =
+            serde_json::json!(orders::crypto::merchant_keys_digest().map(Hex::encode));
+    }
+    (StatusCode::OK, Json(body))
+}
+
+/// Query parameters of `GET /orders/pubkey`.
+#[derive(Debug, Deserialize)]
+pub struct PubkeyParams {
+    /// `pem`, `hex`, or `raw` (base64, the default).
+    pub format: Option<String>,
+}
+
+/// `GET /orders/pubkey`: the enclave public key as SubjectPublicKeyInfo PEM,
+/// hex, or base64 of the raw bytes as served by `/orders/health`.
+pub async fn orders_pubkey(Query(params): Query<PubkeyParams>) -> Result<String, EnclaveError> {
+    use orders::order::key_unavailable;
+    match params.format.as_deref().unwrap_or("raw") {
+        "pem" => orders::crypto::public_key_pem().map_err(key_unavailable),
+        "hex" => Ok(Hex::encode(
+            orders::crypto::public_key_bytes().map_err(key_unavailable)?,
+        )),
+        "raw" | "base64" => orders::crypto::public_key_base64().map_err(key_unavailable),
+        other => Err(EnclaveError::InvalidRequest {
+            code: "unsupported_format",
+            message: format!("format must be pem, hex or raw, got {:?}", other),
+        }),
+    }
+}
+
+pub async fn orders_jwk() -> Result<Json<serde_json::Value>, EnclaveError> {
+    let jwk = orders::crypto::public_key_jwk().map_err(orders::order::key_unavailable)?;
+    Ok(Json(jwk))
+}
+
+pub async fn ready(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    let report = orders::health::check_subsystems(
+        state
+            .audit_log
+            .as_ref()
+            .map(orders::audit::AuditLog::health),
+        state.webhook.as_ref().map(orders::webhook::Webhook::health),
+    );
+    info!(status = ?report.status, "Readiness check");
+    (report.status.status_code(), Json(report))
+}
+
+pub async fn admin_checkin(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Result<Json<serde_json::Value>, EnclaveError> {
+    orders::admin::require_admin(&headers, state.orders_config.admin_token.as_ref())?;
+    state.dead_man.checkin();
+    Ok(Json(serde_json::json!({ "status": "ok" })))
+}
+
+/// `POST /admin/rotate_key`: switch to a fresh signing key. Signatures by
+/// the previous key keep verifying through its `kid`.
+pub async fn admin_rotate_key(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Result<Json<orders::crypto::RotatedKey>, EnclaveError> {
+    orders::admin::require_admin(&headers, state.orders_config.admin_token.as_ref())?;
+    let rotated = orders::crypto::rotate().map_err(orders::order::key_unavailable)?;
+    Ok(Json(rotated))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::num::NonZeroUsize;
+
+    fn store() -> orders::store::OrderStore {
+        orders::store::OrderStore::new(NonZeroUsize::new(16).unwrap())
+    }
+
+    fn request(overrides: serde_json::Value) -> orders::OrderRequest {
+        let mut body = json!({
+            "version": 1,
+            "order_id": "o1",
+            "customer": "alice",
+            "merchant": "acme",
+            "amount": 100,
+            "currency": "USD",
+            "action": "initiate",
+        });
+        body.as_object_mut()
+            .unwrap()
+            .extend(overrides.as_object().unwrap().clone());
+        serde_json::from_value(body).unwrap()
+    }
+
+    #[test]
+    fn well_formed_request_passes() {
+        let config = orders::OrdersConfig::default();
+        assert!(check_request(&config, &store(), &request(json!({}))).is_ok());
+    }
+
+    #[test]
+    fn field_validation_applies() {
+        let config = orders::OrdersConfig::default();
+        let req = request(json!({ "customer": "" }));
+        assert!(matches!(
+            check_request(&config, &store(), &req),
+            Err(EnclaveError::ValidationFailed(_))
+        ));
+    }
+
+    #[test]
+    fn metadata_limits_apply() {
+        let config = orders::OrdersConfig {
+            max_metadata_depth: 1,
+            ..Default::default()
+        };
+        let req = request(json!({ "metadata": { "a": { "b": 1 } } }));
+        assert!(matches!(
+            check_request(&config, &store(), &req),
+            Err(EnclaveError::ValidationFailed(_))
+        ));
+    }
+
+    #[test]
+    fn self_dealing_is_refused_unless_allowed() {
+        let req = request(json!({ "merchant": "Alice " }));
+        let config = orders::OrdersConfig::default();
+        assert!(check_request(&config, &store(), &req).is_err());
+        let config = orders::OrdersConfig {
+            allow_self_dealing: true,
+            ..Default::default()
+        };
+        assert!(check_request(&config, &store(), &req).is_ok());
+    }
+
+    #[test]
+    fn per_merchant_keys_refuse_merchants_off_the_allowlist() {
+        let config = orders::OrdersConfig {
+            per_merchant_keys: true,
+            merchant_allowlist: vec!["acme".to_string()],
+            ..Default::default()
+        };
+        assert!(check_request(&config, &store(), &request(json!({}))).is_ok());
+        let req = request(json!({ "merchant": "made-up" }));
+        assert!(matches!(
+            check_request(&config, &store(), &req),
+            Err(EnclaveError::InvalidRequest {
+                code: "unknown_merchant",
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn strict_protocol_version_pins_one_version() {
+        let req = request(json!({}));
+        let pinned = |version| orders::OrdersConfig {
+            strict_protocol_version: Some(version),
+            ..Default::default()
+        };
+        assert!(check_request(&pinned(1), &store(), &req).is_ok());
+        assert!(matches!(
+            check_request(&pinned(2), &store(), &req),
+            Err(EnclaveError::InvalidRequest {
+                code: "protocol_version_mismatch",
+                ..
+            })
+        ));
+    }
+
+    fn fixed_seed(seed: &mut [u8; 32]) -> Result<(), &'static str> {
+        seed.fill(7);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn filling_state_sheds_new_state_requests() {
+        orders::crypto::ensure_initialized_with(orders::crypto::SchemeKind::Ed25519, fixed_seed)
+            .unwrap();
+        let state = AppState::for_tests(orders::OrdersConfig {
+            max_state_bytes: 1,
+            ..Default::default()
+        });
+        process_one(&state, request(json!({})), false)
+            .await
+            .unwrap();
+
+        let err = process_one(&state, request(json!({ "order_id": "o2" })), false)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            EnclaveError::Unavailable {
+                code: "state_capacity",
+                ..
+            }
+        ));
+        // Lookups keep working while new state is refused.
+        get_order(State(state.clone()), Path("o1".to_string()))
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn processed_order_is_retrievable_and_unknown_is_404() {
+        orders::crypto::ensure_initialized_with(orders::crypto::SchemeKind::Ed25519, fixed_seed)
+            .unwrap();
+        let state = AppState::for_tests(Default::default());
+        let signed = process_one(&state, request(json!({})), false)
+            .await
+            .unwrap();
+
+        let Json(stored) = get_order(State(state.clone()), Path("o1".to_string()))
+            .await
+            .unwrap();
+        assert_eq!(stored["signature"], signed.signature);
+        assert_eq!(stored["response"]["sequence"], signed.response.sequence);
+
+        let err = get_order(State(state), Path("missing".to_string()))
+            .await
+            .unwrap_err();
+        assert_eq!(err.into_status_and_body().0, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn sequential_responses_have_increasing_sequences() {
+        orders::crypto::ensure_initialized_with(orders::crypto::SchemeKind::Ed25519, fixed_seed)
+            .unwrap();
+        let state = AppState::for_tests(Default::default());
+        let first = process_one(&state, request(json!({})), false)
+            .await
+            .unwrap();
+        let second = process_one(&state, request(json!({ "order_id": "o2" })), false)
+            .await
+            .unwrap();
+        assert_eq!(first.response.sequence, 1);
+        assert!(second.response.sequence > first.response.sequence);
+
+        // The sequence is signed: restamping it changes the message.
+        let mut restamped = first.response.clone();
+        restamped.sequence = second.response.sequence;
+        assert_ne!(
+            orders::signing_message(&first.response).unwrap(),
+            orders::signing_message(&restamped).unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn recent_orders_page_back_by_descending_sequence() {
+        orders::crypto::ensure_initialized_with(orders::crypto::SchemeKind::Ed25519, fixed_seed)
+            .unwrap();
+        let state = AppState::for_tests(orders::OrdersConfig {
+            admin_token: Some(orders::config::Secret("t".to_string())),
+            ..Default::default()
+        });
+        let mut sequences = Vec::new();
+        for i in 0..5 {
+            let req = request(json!({ "order_id": format!("o{}", i) }));
+            let signed = process_one(&state, req, false).await.unwrap();
+            sequences.push(signed.response.sequence);
+        }
+        sequences.reverse();
+
+        let mut headers = HeaderMap::new();
+        headers.insert(orders::admin::ADMIN_TOKEN_HEADER, "t".parse().unwrap());
+        let page = |limit, before_seq| {
+            recent_orders(
+                State(state.clone()),
+                headers.clone(),
+                Query(RecentParams {
+                    limit: Some(limit),
+                    before_seq,
+                }),
+            )
+        };
+        let sequences_of = |page: &serde_json::Value| -> Vec<u64> {
+            page["orders"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|signed| signed["response"]["sequence"].as_u64().unwrap())
+                .collect()
+        };
+
+        let Json(first) = page(2, None).await.unwrap();
+        assert_eq!(sequences_of(&first), &sequences[..2]);
+        let Json(second) = page(2, first["next_before_seq"].as_u64()).await.unwrap();
+        assert_eq!(sequences_of(&second), &sequences[2..4]);
+        let Json(last) = page(2, second["next_before_seq"].as_u64()).await.unwrap();
+        assert_eq!(sequences_of(&last), &sequences[4..]);
+        assert!(last["next_before_seq"].is_null());
+    }
+
+    #[tokio::test]
+    async fn dry_run_signs_without_touching_state() {
+        orders::crypto::ensure_initialized_with(orders::crypto::SchemeKind::Ed25519, fixed_seed)
+            .unwrap();
+        let state = AppState::for_tests(Default::default());
+        let signed = process_one(&state, request(json!({ "dry_run": true })), false)
+            .await
+            .unwrap();
+        assert_eq!(signed.response.notes.as_deref(), Some("dry run"));
+        assert_eq!(orders::order::verify_signed_response(&signed), Ok(true));
+
+        assert!(state.order_store.is_empty());
+        assert_eq!(state.sequence.load(Ordering::SeqCst), 0);
+        // The simulated initiate left nothing behind to conflict with.
+        let real = process_one(&state, request(json!({})), false)
+            .await
+            .unwrap();
+        assert_eq!(real.response.sequence, 1);
+    }
+
+    #[tokio::test]
+    async fn orders_past_the_budget_are_still_accepted_once_the_store_is_full() {
+        orders::crypto::ensure_initialized_with(orders::crypto::SchemeKind::Ed25519, fixed_seed)
+            .unwrap();
+        // The smallest budget `OrdersConfig` accepts for these capacities.
+        let capacity = 8;
+        let budget =
+            capacity * (orders::store::MAX_ORDER_BYTES + orders::limits::REPLAY_ENTRY_BYTES) + 1;
+        let vars = [
+            ("ORDER_STORE_CAPACITY", capacity.to_string()),
+            ("NAUTILUS_REPLAY_GUARD_CAPACITY", capacity.to_string()),
+            ("NAUTILUS_MAX_STATE_BYTES", budget.to_string()),
+        ];
+        let config = orders::OrdersConfig::from_lookup(|name| {
+            vars.iter()
+                .find(|(var, _)| *var == name)
+                .map(|(_, value)| value.clone())
+        })
+        .unwrap();
+        let state = AppState::for_tests(config);
+
+        for i in 0..budget / orders::store::MAX_ORDER_BYTES + capacity {
+            let req = request(json!({ "order_id": format!("o{}", i) }));
+            process_one(&state, req, false).await.unwrap();
+        }
+        assert_eq!(state.order_store.len(), capacity);
+        assert!(state.order_store.approx_bytes() < budget);
+    }
+}
@@ -0,0 +1,509 @@
+// Copyright (c), Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::EnclaveError;
+use lru::LruCache;
+use std::num::NonZeroUsize;
+use std::sync::Mutex;
+
+use super::{OrderRequest, OrderStatus, SignedOrderResponse};
+
+/// Rough footprint of one tracked order, terms and action count included,
+/// without a stored response.
+const ORDER_ENTRY_BYTES: usize = 256;
+
+/// Rough footprint of a stored signed response.
+const SIGNED_RESPONSE_BYTES: usize = 1024;
+
+/// Most `approx_bytes` can report per tracked order, so a full store holds
+/// about `capacity * MAX_ORDER_BYTES`.
+pub const MAX_ORDER_BYTES: usize = ORDER_ENTRY_BYTES + SIGNED_RESPONSE_BYTES;
+
+#[derive(Default)]
+struct OrderEntry {
+    /// Last accepted status; `None` if every transition so far was rejected.
+    status: Option<OrderStatus>,
+    /// Last response signed for this order, rejections included.
+    last_signed: Option<SignedOrderResponse>,
+    /// Expiry fixed when the order was initiated.
+    expires_at_ms: Option<u64>,
+    /// Escrowed balance not yet released or refunded.
+    remaining_amount: Option<u64>,
+    /// Terms fixed when the order was initiated.
+    terms: Option<OrderTerms>,
+    /// Actions counted against `max_actions_per_order`, rejections included.
+    actions: u32,
+}
+
+/// The fields of an order that may not change after its `Initiate`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OrderTerms {
+    pub amount: u64,
+    pub currency: String,
+    pub customer: String,
+    pub merchant: String,
+}
+
+impl OrderTerms {
+    pub fn of(req: &OrderRequest) -> Self {
+        Self {
+            amount: req.amount,
+            currency: req.currency.clone(),
+            customer: req.customer.clone(),
+            merchant: req.merchant.clone(),
+        }
+    }
+
+    /// Refuse with 409 `order_terms_mismatch`, naming the first divergent
+    /// field, a request whose terms differ from these.
+    pub fn check(&self, req: &OrderRequest) -> Result<(), EnclaveError> {
+        let divergent = if req.amount != self.amount {
+            Some(("amount", self.amount.to_string(), req.amount.to_string()))
+        } else if req.currency != self.currency {
+            Some(("currency", self.currency.clone(), req.currency.clone()))
+        } else if req.customer != self.customer {
+            Some(("customer", self.customer.clone(), req.customer.clone()))
+        } else if req.merchant != self.merchant {
+            Some(("merchant", self.merchant.clone(), req.merchant.clone()))
+        } else {
+            None
+        };
+        match divergent {
+            None => Ok(()),
+            Some((field, initiated, got)) => Err(EnclaveError::Conflict {
+                code: "order_terms_mismatch",
+                message: format!(
+                    "{} {:?} differs from {:?} fixed when order {} was initiated",
+                    field, got, initiated, req.order_id
+                ),
+            }),
+        }
+    }
+}
+
+/// Per-order state: the last accepted status and escrowed balance backing
+/// the escrow state machine, and the last signed response for
+/// `GET /orders/:order_id`.
+/// Rejected transitions never change the status, so an order keeps its
+/// last good one. Holds at most `capacity` orders; past that the least
+/// recently read or written one is evicted and is unknown from then on,
+/// its action count included.
+pub struct OrderStore {
+    entries: Mutex<Entries>,
+}
+
+struct Entries {
+    lru: LruCache<String, OrderEntry>,
+    /// Entries holding a signed response, kept up to date so `approx_bytes`
+    /// never walks the cache.
+    signed: usize,
+}
+
+impl Entries {
+    fn get(&mut self, order_id: &str) -> Option<&OrderEntry> {
+        self.lru.get(order_id)
+    }
+
+    fn get_mut(&mut self, order_id: &str) -> Option<&mut OrderEntry> {
+        self.lru.get_mut(order_id)
+    }
+
+    /// The entry for `order_id`, created if missing. Evicts the least
+    /// recently touched order first when the cache is full.
+    fn entry(&mut self, order_id: &str) -> &mut OrderEntry {
+        if !self.lru.contains(order_id) && self.lru.len() == self.lru.cap().get() {
+            if let Some((_, evicted)) = self.lru.pop_lru() {
+                self.signed -= usize::from(evicted.last_signed.is_some());
+            }
+        }
+        self.lru
+            .get_or_insert_mut(order_id.to_string(), OrderEntry::default)
+    }
+}
+
+impl OrderStore {
+    pub fn new(capacity: NonZeroUsize) -> Self {
+        Self {
+            entries: Mutex::new(Entries {
+                lru: LruCache::new(capacity),
+                signed: 0,
+            }),
+        }
+    }
+
+    pub fn get(&self, order_id: &str) -> Option<OrderStatus> {
+        self.entries
+            .lock()
+            .expect("order store lock poisoned")
+            .get(order_id)
+            .and_then(|entry| entry.status)
+    }
+
+    /// Escrowed balance left on `order_id`, once it has been deposited.
+    pub fn remaining_amount(&self, order_id: &str) -> Option<u64> {
+        self.entries
+            .lock()
+            .expect("order store lock poisoned")
+            .get(order_id)
+            .and_then(|entry| entry.remaining_amount)
+    }
+
+    /// Move `order_id` to `next` with balance `remaining` if it is still at
+    /// `expected` with balance `expected_remaining`. A concurrent request
+    /// that got there first is refused with 409 `concurrent_update` rather
+    /// than letting both transitions be signed, which also stops two
+    /// installments from jointly over-releasing.
+    pub fn compare_and_set(
+        &self,
+        order_id: &str,
+        expected: Option<OrderStatus>,
+        expected_remaining: Option<u64>,
+        next: OrderStatus,
+        remaining: Option<u64>,
+    ) -> Result<(), EnclaveError> {
+        let mut entries = self.entries.lock().expect("order store lock poisoned");
+        let entry = entries.entry(order_id);
+        if entry.status != expected || entry.remaining_amount != expected_remaining {
+            return Err(EnclaveError::Conflict {
+                code: "concurrent_update",
+                message: format!("order {} changed while being processed", order_id),
+            });
+        }
+        entry.status = Some(next);
+        entry.remaining_amount = remaining;
+        Ok(())
+    }
+
+    /// Put `order_id` back to `prior` with balance `prior_remaining` if it is
+    /// still at the `applied` state a `compare_and_set` moved it to, for a
+    /// transition whose response could not be signed. Undoing an `Initiate`
+    /// (`prior == None`) also drops the expiry and terms it recorded.
+    /// Returns `false`, changing nothing, if the order has moved on since.
+    pub fn revert(
+        &self,
+        order_id: &str,
+        applied: OrderStatus,
+        applied_remaining: Option<u64>,
+        prior: Option<OrderStatus>,
+        prior_remaining: Option<u64>,
+    ) -> bool {
+        let mut entries = self.entries.lock().expect("order store lock poisoned");
+        let Some(entry) = entries.get_mut(order_id) else {
+            return false;
+        };
+        if entry.status != Some(applied) || entry.remaining_amount != applied_remaining {
+            return false;
+        }
+        entry.status = prior;
+        entry.remaining_amount = prior_remaining;
+        if prior.is_none() {
+            entry.expires_at_ms = None;
+            entry.terms = None;
+        }
+        true
+    }
+
+    /// Expiry recorded for `order_id` by its `Initiate`, if any.
+    pub fn expires_at_ms(&self, order_id: &str) -> Option<u64> {
+        self.entries
+            .lock()
+            .expect("order store lock poisoned")
+            .get(order_id)
+            .and_then(|entry| entry.expires_at_ms)
+    }
+
+    pub fn set_expires_at_ms(&self, order_id: &str, expires_at_ms: Option<u64>) {
+        self.entries
+            .lock()
+            .expect("order store lock poisoned")
+            .entry(order_id)
+            .expires_at_ms = expires_at_ms;
+    }
+
+    /// Terms recorded for `order_id` by its `Initiate`, if any.
+    pub fn terms(&self, order_id: &str) -> Option<OrderTerms> {
+        self.entries
+            .lock()
+            .expect("order store lock poisoned")
+            .get(order_id)
+            .and_then(|entry| entry.terms.clone())
+    }
+
+    pub fn set_terms(&self, order_id: &str, terms: OrderTerms) {
+        self.entries
+            .lock()
+            .expect("order store lock poisoned")
+            .entry(order_id)
+            .terms = Some(terms);
+    }
+
+    /// Count one more action on `order_id`, or refuse with
+    /// `order_action_limit_exceeded` once it has had `max_actions`, so one
+    /// id cannot be driven through endless transitions to grow logs and
+    /// state.
+    pub fn try_count_action(&self, order_id: &str, max_actions: u32) -> Result<(), EnclaveError> {
+        let mut entries = self.entries.lock().expect("order store lock poisoned");
+        let entry = entries.entry(order_id);
+        if entry.actions >= max_actions {
+            return Err(EnclaveError::InvalidRequest {
+                code: "order_action_limit_exceeded",
+                message: format!(
+                    "order {} has reached the limit of {} actions",
+                    order_id, max_actions
+                ),
+            });
+        }
+        entry.actions += 1;
+        Ok(())
+    }
+
+    /// Give back the action `try_count_action` counted on `order_id`, for
+    /// an action that was not signed after all.
+    pub fn forget_action(&self, order_id: &str) {
+        let mut entries = self.entries.lock().expect("order store lock poisoned");
+        if let Some(entry) = entries.get_mut(order_id) {
+            entry.actions = entry.actions.saturating_sub(1);
+        }
+    }
+
+    /// Keep `signed` as the latest response for its order, exactly as
+    /// returned, so reading it back never needs a fresh signature.
+    pub fn record_signed(&self, signed: &SignedOrderResponse) {
+        let mut entries = self.entries.lock().expect("order store lock poisoned");
+        let entry = entries.entry(&signed.response.order_id);
+        let newly_signed = entry.last_signed.replace(signed.clone()).is_none();
+        entries.signed += usize::from(newly_signed);
+    }
+
+    /// Number of orders tracked.
+    pub fn len(&self) -> usize {
+        self.entries
+            .lock()
+            .expect("order store lock poisoned")
+            .lru
+            .len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The last signed response for `order_id`, if any.
+    pub fn last_signed(&self, order_id: &str) -> Option<SignedOrderResponse> {
+        self.entries
+            .lock()
+            .expect("order store lock poisoned")
+            .get(order_id)
+            .and_then(|entry| entry.last_signed.clone())
+    }
+
+    /// Up to `limit` last-signed responses with a sequence below
+    /// `before_seq` (any, if `None`), highest sequence first. Does not count
+    /// as touching the orders.
+    pub fn recent(&self, limit: usize, before_seq: Option<u64>) -> Vec<SignedOrderResponse> {
+        let entries = self.entries.lock().expect("order store lock poisoned");
+        let mut recent: Vec<&SignedOrderResponse> = entries
+            .lru
+            .iter()
+            .filter_map(|(_, entry)| entry.last_signed.as_ref())
+            .filter(|signed| before_seq.is_none_or(|before| signed.response.sequence < before))
+            .collect();
+        recent.sort_unstable_by(|a, b| b.response.sequence.cmp(&a.response.sequence));
+        recent.into_iter().take(limit).cloned().collect()
+    }
+
+    /// Approximate memory held by the store, at most `capacity *
+    /// MAX_ORDER_BYTES`.
+    pub fn approx_bytes(&self) -> usize {
+        let entries = self.entries.lock().expect("order store lock poisoned");
+        entries.lru.len() * ORDER_ENTRY_BYTES + entries.signed * SIGNED_RESPONSE_BYTES
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn store() -> OrderStore {
+        OrderStore::new(NonZeroUsize::new(16).unwrap())
+    }
+
+    fn terms() -> OrderTerms {
+        OrderTerms {
+            amount: 100,
+            currency: "USD".to_string(),
+            customer: "alice".to_string(),
+            merchant: "acme".to_string(),
+        }
+    }
+
+    #[test]
+    fn revert_undoes_an_initiate() {
+        let store = store();
+        store
+            .compare_and_set("o1", None, None, OrderStatus::Pending, None)
+            .unwrap();
+        store.set_expires_at_ms("o1", Some(5));
+        store.set_terms("o1", terms());
+
+        assert!(store.revert("o1", OrderStatus::Pending, None, None, None));
+        assert_eq!(store.get("o1"), None);
+        assert_eq!(store.expires_at_ms("o1"), None);
+        assert_eq!(store.terms("o1"), None);
+    }
+
+    #[test]
+    fn revert_restores_the_prior_balance() {
+        let store = store();
+        store
+            .compare_and_set("o1", None, None, OrderStatus::Escrowed, Some(100))
+            .unwrap();
+        store
+            .compare_and_set(
+                "o1",
+                Some(OrderStatus::Escrowed),
+                Some(100),
+                OrderStatus::Escrowed,
+                Some(40),
+            )
+            .unwrap();
+
+        assert!(store.revert(
+            "o1",
+            OrderStatus::Escrowed,
+            Some(40),
+            Some(OrderStatus::Escrowed),
+            Some(100)
+        ));
+        assert_eq!(store.get("o1"), Some(OrderStatus::Escrowed));
+        assert_eq!(store.remaining_amount("o1"), Some(100));
+    }
+
+    #[test]
+    fn revert_leaves_an_order_that_moved_on() {
+        let store = store();
+        store
+            .compare_and_set("o1", None, None, OrderStatus::Pending, None)
+            .unwrap();
+        store
+            .compare_and_set(
+                "o1",
+                Some(OrderStatus::Pending),
+                None,
+                OrderStatus::Escrowed,
+                Some(100),
+            )
+            .unwrap();
+
+        assert!(!store.revert("o1", OrderStatus::Pending, None, None, None));
+        assert_eq!(store.get("o1"), Some(OrderStatus::Escrowed));
+    }
+
+    #[test]
+    fn compare_and_set_refuses_a_stale_expectation() {
+        let store = store();
+        store
+            .compare_and_set("o1", None, None, OrderStatus::Pending, None)
+            .unwrap();
+        let err = store
+            .compare_and_set("o1", None, None, OrderStatus::Pending, None)
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            EnclaveError::Conflict {
+                code: "concurrent_update",
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn least_recently_touched_orders_are_evicted_past_capacity() {
+        let store = OrderStore::new(NonZeroUsize::new(3).unwrap());
+        for id in ["o1", "o2", "o3"] {
+            store
+                .compare_and_set(id, None, None, OrderStatus::Pending, None)
+                .unwrap();
+        }
+        // Reading o1 makes o2 the least recently touched.
+        assert_eq!(store.get("o1"), Some(OrderStatus::Pending));
+        for id in ["o4", "o5"] {
+            store
+                .compare_and_set(id, None, None, OrderStatus::Pending, None)
+                .unwrap();
+        }
+
+        assert_eq!(store.len(), 3);
+        assert_eq!(store.get("o2"), None);
+        assert_eq!(store.get("o3"), None);
+        for id in ["o1", "o4", "o5"] {
+            assert_eq!(store.get(id), Some(OrderStatus::Pending), "{}", id);
+        }
+    }
+
+    fn follow_up(action: &str, amount: u64) -> OrderRequest {
+        serde_json::from_value(serde_json::json!({
+            "version": 1,
+            "order_id": "o1",
+            "customer": "alice",
+            "merchant": "acme",
+            "amount": amount,
+            "currency": "USD",
+            "action": action,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn follow_ups_must_keep_the_initiated_terms() {
+        let terms = terms();
+        assert!(terms.check(&follow_up("deposit", 100)).is_ok());
+        assert!(terms.check(&follow_up("release", 100)).is_ok());
+
+        match terms.check(&follow_up("deposit", 101)) {
+            Err(EnclaveError::Conflict {
+                code: "order_terms_mismatch",
+                message,
+            }) => assert!(
+                message.starts_with("amount \"101\" differs from \"100\""),
+                "{}",
+                message
+            ),
+            other => panic!("expected order_terms_mismatch, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn action_count_refuses_past_the_limit() {
+        let store = store();
+        for _ in 0..3 {
+            store.try_count_action("o1", 3).unwrap();
+        }
+        assert!(matches!(
+            store.try_count_action("o1", 3),
+            Err(EnclaveError::InvalidRequest {
+                code: "order_action_limit_exceeded",
+                ..
+            })
+        ));
+        // The limit is per order id, and a forgotten action is given back.
+        store.try_count_action("o2", 3).unwrap();
+        store.forget_action("o1");
+        store.try_count_action("o1", 3).unwrap();
+    }
+
+    #[test]
+    fn approx_bytes_stays_bounded_as_orders_are_evicted() {
+        let store = OrderStore::new(NonZeroUsize::new(2).unwrap());
+        for id in ["o1", "o2", "o3", "o4"] {
+            store.try_count_action(id, 16).unwrap();
+        }
+        assert_eq!(store.len(), 2);
+        assert_eq!(store.approx_bytes(), 2 * ORDER_ENTRY_BYTES);
+        // Counting an action touches the order, so o5 evicts o3, not o4.
+        store.try_count_action("o4", 16).unwrap();
+        store.try_count_action("o5", 16).unwrap();
+        assert!(store.try_count_action("o4", 2).is_err());
+        assert_eq!(store.approx_bytes(), 2 * ORDER_ENTRY_BYTES);
+    }
+}
@@ -0,0 +1,151 @@
+// Copyright (c), Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use base64::engine::general_purpose::STANDARD as B64;
+use base64::Engine;
+use fastcrypto::encoding::{Encoding, Hex};
+use std::fmt;
+
+use super::crypto::{self, SchemeKind};
+use super::order::{message_digest, signing_message_for, SignedOrderResponse, SigningFormat};
+
+/// Why a `SignedOrderResponse` failed client-side verification.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VerifyError {
+    /// Well-formed, but the signature does not cover this response under
+    /// the embedded key (or `digest` does not match the response).
+    BadSignature,
+    /// `public_key` is not base64, is the wrong length for `scheme`, or is
+    /// not a valid point.
+    MalformedKey,
+    /// `signature` (or `digest`) is not valid base64 / hex of the right length.
+    MalformedSignature,
+    /// `scheme` or `alg` names something this verifier does not implement.
+    UnsupportedScheme(String),
+}
+
+impl fmt::Display for VerifyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VerifyError::BadSignature => write!(f, "signature does not verify"),
+            VerifyError::MalformedKey => write!(f, "public key is malformed"),
+            VerifyError::MalformedSignature => write!(f, "signature is malformed"),
+            VerifyError::UnsupportedScheme(name) => write!(f, "unsupported scheme: {}", name),
+        }
+    }
+}
+
+impl std::error::Error for VerifyError {}
+
+/// Verify `signed` against the public key it carries, without any server
+/// state: rebuild the signing message named by `alg` from `response`
+/// (`BCS(IntentMessage)` or the JCS form), hash it first when `digest` is
+/// present, and check `signature` under `scheme`.
+///
+/// This only proves the response was signed by `public_key`; callers must
+/// still pin that key, e.g. to the attested one or its fingerprint.
+pub fn verify_signed_response(signed: &SignedOrderResponse) -> Result<(), VerifyError> {
+    let scheme: SchemeKind = signed
+        .scheme
+        .parse()
+        .map_err(|_| VerifyError::UnsupportedScheme(signed.scheme.clone()))?;
+    let format: SigningFormat = signed
+        .alg
+        .parse()
+        .map_err(|_| VerifyError::UnsupportedScheme(signed.alg.clone()))?;
+
+    let key_len = B64
+        .decode(signed.public_key.trim())
+        .map_err(|_| VerifyError::MalformedKey)?
+        .len();
+    let expected_len = match scheme {
+        SchemeKind::Ed25519 => 32,
+        SchemeKind::Secp256k1 => 33,
+    };
+    if key_len != expected_len {
+        return Err(VerifyError::MalformedKey);
+    }
+
+    let signature: [u8; 64] = B64
+        .decode(signed.signature.trim())
+        .map_err(|_| VerifyError::MalformedSignature)?
+        .try_into()
+        .map_err(|_| VerifyError::MalformedSignature)?;
+
+    // A deserialized response always encodes; failing here means the
+    // response cannot be what was signed.
+    let mut message =
+        signing_message_for(&signed.response, format).map_err(|_| VerifyError::BadSignature)?;
+    if let Some(claimed) = &signed.digest {
+        let claimed = Hex::decode(claimed.trim()).map_err(|_| VerifyError::MalformedSignature)?;
+        let digest = message_digest(&message);
+        if claimed != digest {
+            return Err(VerifyError::BadSignature);
+        }
+        message = digest.to_vec();
+    }
+
+    match crypto::verify(&message, &signature, &signed.public_key) {
+        Ok(true) => Ok(()),
+        Ok(false) => Err(VerifyError::BadSignature),
+        Err(_) => Err(VerifyError::MalformedKey),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::orders::{make_response, sign_response, OrderRequest};
+
+    fn fixed_seed(seed: &mut [u8; 32]) -> Result<(), &'static str> {
+        seed.fill(7);
+        Ok(())
+    }
+
+    fn signed() -> SignedOrderResponse {
+        crypto::ensure_initialized_with(SchemeKind::Ed25519, fixed_seed).unwrap();
+        let req: OrderRequest = serde_json::from_value(serde_json::json!({
+            "version": 1,
+            "order_id": "o1",
+            "customer": "alice",
+            "merchant": "acme",
+            "amount": 100,
+            "currency": "USD",
+            "action": "initiate",
+        }))
+        .unwrap();
+        sign_response(&make_response(&req).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn untouched_response_verifies() {
+        assert_eq!(verify_signed_response(&signed()), Ok(()));
+    }
+
+    #[test]
+    fn tampered_amount_is_a_bad_signature() {
+        let mut signed = signed();
+        signed.response.amount += 1;
+        assert_eq!(
+            verify_signed_response(&signed),
+            Err(VerifyError::BadSignature)
+        );
+    }
+
+    #[test]
+    fn swapped_public_key_is_a_bad_signature() {
+        let mut signed = signed();
+        let other = ed25519_dalek::SigningKey::from_bytes(&[9; 32]);
+        signed.public_key = B64.encode(other.verifying_key().to_bytes());
+        assert_eq!(
+            verify_signed_response(&signed),
+            Err(VerifyError::BadSignature)
+        );
+
+        signed.public_key = B64.encode([1; 31]);
+        assert_eq!(
+            verify_signed_response(&signed),
+            Err(VerifyError::MalformedKey)
+        );
+    }
+}
@@ -0,0 +1,204 @@
+// Copyright (c), Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+use fastcrypto::encoding::{Encoding, Hex};
+use hmac::{Hmac, Mac};
+use reqwest::Client;
+use sha2::Sha256;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tracing::{error, info, warn};
+
+use super::health::HealthFlag;
+use super::{OrdersConfig, SignedOrderResponse};
+use crate::metrics;
+
+/// Header carrying `sha256=<hex HMAC-SHA256(WEBHOOK_SECRET, body)>`.
+pub const SIGNATURE_HEADER: &str = "x-nautilus-signature";
+
+/// Notifications that may queue up behind a slow receiver before new ones
+/// are dropped.
+const WEBHOOK_QUEUE_CAPACITY: usize = 1024;
+/// Attempts per notification, and the wait before the first retry; each
+/// further retry waits twice as long.
+const MAX_ATTEMPTS: u32 = 3;
+const INITIAL_BACKOFF: Duration = Duration::from_millis(250);
+
+/// Pushes every signed response to `WEBHOOK_URL`. Requests only enqueue the
+/// body; a background task delivers it, so a slow or failing receiver never
+/// delays or fails an order. Deliveries that fail every attempt are logged
+/// and counted, then dropped, and flag the webhook degraded in `/ready`
+/// until one succeeds.
+pub struct Webhook {
+    tx: mpsc::Sender<Vec<u8>>,
+    health: HealthFlag,
+}
+
+impl Webhook {
+    /// `None` unless both `WEBHOOK_URL` and `WEBHOOK_SECRET` are configured;
+    /// setting only one of them is an error.
+    pub fn from_config(config: &OrdersConfig) -> Result<Option<Self>, String> {
+        let (url, secret) = match (&config.webhook_url, &config.webhook_secret) {
+            (None, None) => return Ok(None),
+            (Some(url), Some(secret)) => (url.clone(), secret.0.clone().into_bytes()),
+            _ => return Err("WEBHOOK_URL and WEBHOOK_SECRET must be set together".to_string()),
+        };
+        let client = Client::builder()
+            .timeout(Duration::from_secs(2))
+            .build()
+            .map_err(|e| format!("webhook client: {}", e))?;
+        let (tx, rx) = mpsc::channel(WEBHOOK_QUEUE_CAPACITY);
+        let health = HealthFlag::default();
+        info!(url = %url, "Webhook enabled");
+        tokio::spawn(deliver(client, url, secret, rx, health.clone()));
+        Ok(Some(Self { tx, health }))
+    }
+
+    /// Whether notifications are currently being delivered.
+    pub fn health(&self) -> &HealthFlag {
+        &self.health
+    }
+
+    /// Queue `signed` for delivery. Never waits.
+    pub fn notify(&self, signed: &SignedOrderResponse) {
+        let body = match serde_json::to_vec(signed) {
+            Ok(body) => body,
+            Err(e) => {
+                let order_id = &signed.response.order_id;
+                error!(order_id = %order_id, error = %e, "Webhook body encoding failed");
+                return;
+            }
+        };
+        if let Err(e) = self.tx.try_send(body) {
+            self.health.fail();
+            metrics::record_webhook_failure("queue_full");
+            let order_id = &signed.response.order_id;
+            error!(order_id = %order_id, error = %e, "Webhook notification dropped");
+        }
+    }
+}
+
+/// `sha256=` followed by the hex HMAC-SHA256 of `body` under `secret`.
+pub fn signature(secret: &[u8], body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret).expect("HMAC accepts keys of any length");
+    mac.update(body);
+    format!("sha256={}", Hex::encode(mac.finalize().into_bytes()))
+}
+
+/// Deliver queued bodies one at a time, retrying each with backoff.
+async fn deliver(
+    client: Client,
+    url: String,
+    secret: Vec<u8>,
+    mut rx: mpsc::Receiver<Vec<u8>>,
+    health: HealthFlag,
+) {
+    while let Some(body) = rx.recv().await {
+        let signature = signature(&secret, &body);
+        let mut backoff = INITIAL_BACKOFF;
+        for attempt in 1..=MAX_ATTEMPTS {
+            let result = client
+                .post(&url)
+                .header(reqwest::header::CONTENT_TYPE, "application/json")
+                .header(SIGNATURE_HEADER, &signature)
+                .body(body.clone())
+                .send()
+                .await
+                .and_then(|r| r.error_for_status());
+            match result {
+                Ok(_) => {
+                    health.recover();
+                    break;
+                }
+                Err(e) if attempt < MAX_ATTEMPTS => {
+                    warn!(attempt, error = %e, "Webhook delivery failed; retrying");
+                    tokio::time::sleep(backoff).await;
+                    backoff *= 2;
+                }
+                Err(e) => {
+                    health.fail();
+                    metrics::record_webhook_failure("delivery");
+                    error!(attempts = MAX_ATTEMPTS, error = %e, "Webhook delivery abandoned");
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::orders::config::Secret;
+    use crate::orders::{crypto, make_response, sign_response, OrderRequest};
+
+    fn fixed_seed(seed: &mut [u8; 32]) -> Result<(), &'static str> {
+        seed.fill(7);
+        Ok(())
+    }
+
+    /// Serve a receiver that forwards the signature header and body of each
+    /// notification to the returned channel, with the URL to post to.
+    async fn mock_receiver() -> (String, mpsc::Receiver<(String, Vec<u8>)>) {
+        let (tx, rx) = mpsc::channel(1);
+        let app = axum::Router::new().route(
+            "/hook",
+            axum::routing::post(
+                move |headers: axum::http::HeaderMap, body: axum::body::Bytes| async move {
+                    let signature = headers[SIGNATURE_HEADER].to_str().unwrap().to_string();
+                    tx.send((signature, body.to_vec())).await.unwrap();
+                },
+            ),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        (format!("http://{}/hook", addr), rx)
+    }
+
+    #[tokio::test]
+    async fn notification_carries_a_valid_hmac() {
+        crypto::ensure_initialized_with(crypto::SchemeKind::Ed25519, fixed_seed).unwrap();
+        let (url, mut received) = mock_receiver().await;
+        let webhook = Webhook::from_config(&OrdersConfig {
+            webhook_url: Some(url),
+            webhook_secret: Some(Secret("hook-secret".to_string())),
+            ..Default::default()
+        })
+        .unwrap()
+        .unwrap();
+
+        let req: OrderRequest = serde_json::from_value(serde_json::json!({
+            "version": 1,
+            "order_id": "hook-o1",
+            "customer": "alice",
+            "merchant": "acme",
+            "amount": 100,
+            "currency": "USD",
+            "action": "initiate",
+        }))
+        .unwrap();
+        let signed = sign_response(&make_response(&req).unwrap()).unwrap();
+        webhook.notify(&signed);
+
+        let (header, body) = tokio::time::timeout(Duration::from_secs(5), received.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        let delivered: SignedOrderResponse = serde_json::from_slice(&body).unwrap();
+        assert_eq!(delivered.signature, signed.signature);
+
+        let mac = header.strip_prefix("sha256=").unwrap();
+        let mut expected = Hmac::<Sha256>::new_from_slice(b"hook-secret").unwrap();
+        expected.update(&body);
+        expected.verify_slice(&Hex::decode(mac).unwrap()).unwrap();
+    }
+
+    #[test]
+    fn half_configured_webhook_is_an_error() {
+        let config = OrdersConfig {
+            webhook_url: Some("http://127.0.0.1:1/hook".to_string()),
+            ..Default::default()
+        };
+        assert!(Webhook::from_config(&config).is_err());
+    }
+}
@@ -0,0 +1,85 @@
+// Copyright (c), Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Per-request correlation ids. Each request runs inside a `request` span
+//! carrying its id, so every log line it produces can be grouped, and the
+//! id is echoed back in the response headers.
+
+use axum::extract::Request;
+use axum::http::{HeaderName, HeaderValue};
+use axum::middleware::Next;
+use axum::response::Response;
+use tracing::{info_span, Instrument};
+
+pub const DEFAULT_REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Longest client-supplied id we pass through; longer ones are replaced.
+const MAX_REQUEST_ID_LEN: usize = 128;
+
+/// Middleware body: keep a well-formed client id from `header`, otherwise
+/// mint a UUIDv4, run the rest of the stack inside a span carrying it, and
+/// set it on the response.
+pub async fn propagate(header: HeaderName, req: Request, next: Next) -> Response {
+    let id = req
+        .headers()
+        .get(&header)
+        .and_then(|v| v.to_str().ok())
+        .filter(|id| !id.is_empty() && id.len() <= MAX_REQUEST_ID_LEN)
+        .map(str::to_string)
+        .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+    let span = info_span!(
+        "request",
+        request_id = %id,
+        method = %req.method(),
+        path = %req.uri().path(),
+    );
+    let mut response = next.run(req).instrument(span).await;
+    if let Ok(value) = HeaderValue::from_str(&id) {
+        response.headers_mut().insert(header, value);
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use axum::routing::get;
+    use axum::Router;
+    use tower::ServiceExt;
+
+    fn app(header: &'static str) -> Router {
+        Router::new()
+            .route("/", get(|| async { "ok" }))
+            .layer(axum::middleware::from_fn(
+                move |req: Request, next: Next| {
+                    propagate(HeaderName::from_static(header), req, next)
+                },
+            ))
+    }
+
+    async fn echoed(header: &'static str, supplied: Option<&str>) -> String {
+        let mut req = Request::get("/");
+        if let Some(id) = supplied {
+            req = req.header(header, id);
+        }
+        let response = app(header)
+            .oneshot(req.body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        response.headers()[header].to_str().unwrap().to_string()
+    }
+
+    #[tokio::test]
+    async fn client_supplied_id_is_echoed_unchanged() {
+        assert_eq!(echoed("x-correlation-id", Some("abc-123")).await, "abc-123");
+    }
+
+    #[tokio::test]
+    async fn missing_or_oversized_id_is_replaced() {
+        let minted = echoed(DEFAULT_REQUEST_ID_HEADER, None).await;
+        assert!(uuid::Uuid::parse_str(&minted).is_ok());
+        let long = "a".repeat(MAX_REQUEST_ID_LEN + 1);
+        assert_ne!(echoed(DEFAULT_REQUEST_ID_HEADER, Some(&long)).await, long);
+    }
+}
@@ -0,0 +1,68 @@
+// Copyright (c), Mysten Labs, Inc.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Rotation replaces the process-wide signing key, so it is tested in its
+//! own binary: the unit tests share that key and must never see it change.
+
+use axum::body::to_bytes;
+use axum::response::IntoResponse;
+use axum::Json;
+use nautilus_server::orders::crypto::{self, SchemeKind};
+use nautilus_server::orders::order::SIGNING_FIELD_VERSION;
+use nautilus_server::orders::routes::verify_order;
+use nautilus_server::orders::{
+    sign_response, OrderAction, OrderStatus, SignableOrderResponse, SignedOrderResponse,
+};
+use serde_json::Value;
+
+fn response() -> SignableOrderResponse {
+    SignableOrderResponse {
+        field_version: SIGNING_FIELD_VERSION,
+        version: 1,
+        order_id: "o1".to_string(),
+        action: OrderAction::Initiate,
+        status: OrderStatus::Pending,
+        amount: 100,
+        currency: "USD".to_string(),
+        server_timestamp_ms: 1_700_000_000_000,
+        escrow_tx_id: None,
+        notes: None,
+        client_reference: None,
+        sequence: 1,
+        expires_at_ms: None,
+        remaining_amount: None,
+        request_digest: String::new(),
+        fee_amount: 0,
+        net_amount: 100,
+        metadata_digest: None,
+        settle_amount: None,
+    }
+}
+
+/// The JSON body `POST /orders/verify` answers `signed` with.
+async fn verify(signed: SignedOrderResponse) -> Value {
+    let body = verify_order(Json(signed)).await.into_response().into_body();
+    serde_json::from_slice(&to_bytes(body, usize::MAX).await.unwrap()).unwrap()
+}
+
+#[tokio::test]
+async fn signature_by_a_rotated_out_key_still_verifies() {
+    crypto::ensure_initialized(SchemeKind::Ed25519, None).unwrap();
+    let signed = sign_response(&response()).unwrap();
+
+    let rotated = crypto::rotate().unwrap();
+    assert_eq!(rotated.retired_kid, signed.kid);
+    assert_ne!(rotated.kid, signed.kid);
+    let retired = crypto::retired_keys();
+    let old = retired.iter().find(|key| key.kid == signed.kid).unwrap();
+    assert_eq!(old.public_key, signed.public_key);
+
+    assert_eq!(verify(signed.clone()).await["valid"], true);
+    let mut tampered = signed;
+    tampered.response.amount += 1;
+    assert_eq!(verify(tampered).await["valid"], false);
+
+    let fresh = sign_response(&response()).unwrap();
+    assert_eq!(fresh.kid, rotated.kid);
+    assert_eq!(verify(fresh).await["valid"], true);
+}